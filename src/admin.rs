@@ -0,0 +1,158 @@
+use crate::{webhooks::WebhookView, AppState, TickState, VotesView};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use std::sync::Arc;
+
+/// Stop game ticks until resumed.
+pub async fn handle_pause(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    *app.paused.lock().await = true;
+    println!("Game paused.");
+    StatusCode::NO_CONTENT
+}
+
+pub async fn handle_resume(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    *app.paused.lock().await = false;
+    println!("Game resumed.");
+    StatusCode::NO_CONTENT
+}
+
+/// Unregister bot by name.
+pub async fn handle_kick(
+    Extension(app): Extension<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match app.bots.lock().await.kick(&name) {
+        Ok(_) => {
+            println!("Bot kicked: {name}");
+            StatusCode::NO_CONTENT
+        }
+        Err(_) => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Drop votes pending for next tick.
+pub async fn handle_reset_votes(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    app.selected_moves.lock().await.clear();
+    app.presence.lock().await.clear_tick_voters();
+    StatusCode::NO_CONTENT
+}
+
+/// Effective server settings.
+#[derive(serde::Serialize)]
+struct ConfigView {
+    listeners: Vec<String>,
+    level_width: usize,
+    level_height: usize,
+    frame_time_ms: u128,
+    topology: &'static str,
+    boundary: &'static str,
+    steering: &'static str,
+    bot_driving: &'static str,
+    vote_strategy: String,
+    anonymous_role: Option<crate::auth::Role>,
+    static_tokens: usize,
+    webhooks: usize,
+}
+
+pub async fn handle_config(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Json(ConfigView {
+        listeners: app.listeners.clone(),
+        level_width: crate::LEVEL_WIDTH,
+        level_height: crate::LEVEL_HEIGHT,
+        frame_time_ms: crate::FRAME_TIME.as_millis(),
+        topology: app.topology.name(),
+        boundary: app.boundary.name(),
+        steering: app.steering.name(),
+        bot_driving: app.bot_driving.name(),
+        vote_strategy: app.strategy.to_string(),
+        anonymous_role: app.auth.anonymous(),
+        static_tokens: app.auth.tokens_count(),
+        webhooks: app.webhooks.lock().await.len(),
+    })
+}
+
+/// Current game, votes and bots in single response.
+#[derive(serde::Serialize)]
+struct Snapshot {
+    paused: bool,
+    state: Option<TickState>,
+    votes: VotesView,
+    bots: Vec<String>,
+    driver: Option<String>,
+}
+
+pub async fn handle_snapshot(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let state = app.state_recv.borrow().clone();
+    let votes = crate::votes_view(&app).await;
+    let (bots, driver) = {
+        let bots = app.bots.lock().await;
+        (bots.names(), bots.driver_name().map(str::to_owned))
+    };
+
+    Json(Snapshot {
+        paused: *app.paused.lock().await,
+        state,
+        votes,
+        bots,
+        driver,
+    })
+}
+
+pub async fn handle_webhooks(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Json(app.webhooks.lock().await.list())
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewWebhook {
+    url: String,
+    /// generated when not given
+    secret: Option<String>,
+    /// subscribed events, all when empty
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct WebhookAdded {
+    #[serde(flatten)]
+    webhook: WebhookView,
+    /// returned only on registration
+    secret: String,
+}
+
+/// Register webhook receiving signed game events.
+pub async fn handle_add_webhook(
+    Extension(app): Extension<Arc<AppState>>,
+    Json(new): Json<NewWebhook>,
+) -> Response {
+    let registered = app
+        .webhooks
+        .lock()
+        .await
+        .register(&new.url, new.secret, new.events);
+    match registered {
+        Ok((webhook, secret)) => {
+            println!("Webhook registered: {}", webhook.url);
+            (StatusCode::CREATED, Json(WebhookAdded { webhook, secret })).into_response()
+        }
+        Err(error) => {
+            let error = serde_json::json!({ "error": error });
+            (StatusCode::BAD_REQUEST, Json(error)).into_response()
+        }
+    }
+}
+
+pub async fn handle_remove_webhook(
+    Extension(app): Extension<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    if app.webhooks.lock().await.remove(id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
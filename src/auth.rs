@@ -0,0 +1,496 @@
+use crate::listen::PeerAddr;
+use crate::presence::Client;
+use crate::AppState;
+use axum::{
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SESSION_COOKIE: &str = "snapi_session";
+/// lifetime of session cookie
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const SECRET_LENGTH: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Access level, each role has permissions of lower roles.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// watch the game
+    Viewer,
+    /// vote and register bots
+    Voter,
+    /// pause the game, kick bots, reset votes
+    Moderator,
+    /// read server config and snapshots
+    Admin,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Voter => "voter",
+            Self::Moderator => "moderator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "voter" => Ok(Self::Voter),
+            "moderator" => Ok(Self::Moderator),
+            "admin" => Ok(Self::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Static tokens with their roles and key signing session cookies.
+pub struct Auth {
+    tokens: HashMap<String, Role>,
+    secret: Vec<u8>,
+    /// role of requests without credentials, `None` when credentials are required
+    anonymous: Option<Role>,
+    /// session cookie is sent over TLS only
+    secure_cookie: bool,
+}
+
+impl Default for Auth {
+    /// No static tokens, anyone can vote, sessions are signed with random key.
+    fn default() -> Self {
+        Self {
+            tokens: HashMap::new(),
+            secret: random_secret(),
+            anonymous: Some(Role::Voter),
+            secure_cookie: false,
+        }
+    }
+}
+
+fn random_secret() -> Vec<u8> {
+    use rand::RngCore;
+
+    let mut secret = vec![0; SECRET_LENGTH];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// Compare secrets without revealing position of first difference (length is not hidden).
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl Auth {
+    /// Settings from `SNAPI_TOKENS` (`role:token` pairs separated with commas),
+    /// `SNAPI_SESSION_SECRET` and `SNAPI_ANONYMOUS_ROLE` (role or `none`) env variables.
+    pub fn from_env() -> Result<Self, String> {
+        let mut auth = Self::default();
+
+        if let Ok(tokens) = std::env::var("SNAPI_TOKENS") {
+            auth.tokens = Self::parse_tokens(&tokens)?;
+        }
+        match std::env::var("SNAPI_SESSION_SECRET") {
+            Ok(secret) if !secret.is_empty() => auth.secret = secret.into_bytes(),
+            _ => println!("Session secret not set, sessions expire on restart."),
+        }
+        if let Ok(role) = std::env::var("SNAPI_ANONYMOUS_ROLE") {
+            auth.anonymous = match role.as_str() {
+                "none" => None,
+                role => Some(
+                    role.parse()
+                        .map_err(|_| format!("Unknown anonymous role: {role}"))?,
+                ),
+            };
+        }
+
+        Ok(auth)
+    }
+
+    /// Mark session cookie `Secure`, for server listening over TLS.
+    pub fn set_secure_cookie(&mut self, secure: bool) {
+        self.secure_cookie = secure;
+    }
+
+    /// `Set-Cookie` header value with session cookie, empty value removes the cookie.
+    fn session_cookie(&self, value: &str, max_age: u64) -> String {
+        let secure = if self.secure_cookie { "; Secure" } else { "" };
+        format!("{SESSION_COOKIE}={value}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Strict{secure}")
+    }
+
+    fn parse_tokens(value: &str) -> Result<HashMap<String, Role>, String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (role, token) = pair
+                    .split_once(':')
+                    .ok_or_else(|| format!("Token without role: {pair}"))?;
+                let role: Role = role
+                    .parse()
+                    .map_err(|_| format!("Unknown token role: {role}"))?;
+                if token.is_empty() {
+                    return Err(format!("Empty token for role: {}", role.name()));
+                }
+                Ok((token.to_owned(), role))
+            })
+            .collect()
+    }
+
+    pub fn anonymous(&self) -> Option<Role> {
+        self.anonymous
+    }
+
+    pub fn tokens_count(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Role of static token, all tokens are compared in constant time.
+    fn token_role(&self, token: &str) -> Option<Role> {
+        self.tokens.iter().fold(None, |found, (known, role)| {
+            if constant_time_eq(known.as_bytes(), token.as_bytes()) {
+                Some(*role)
+            } else {
+                found
+            }
+        })
+    }
+
+    fn signature(&self, payload: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("hmac accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    /// Signed session cookie value `role.expires.signature` valid until `expires` (unix time).
+    fn session(&self, role: Role, expires: u64) -> String {
+        let payload = format!("{}.{expires}", role.name());
+        let signature = self
+            .signature(&payload)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        format!("{payload}.{signature}")
+    }
+
+    /// Role of session cookie value, `None` for invalid or expired session.
+    fn session_role(&self, session: &str, now: u64) -> Option<Role> {
+        let (payload, signature) = session.rsplit_once('.')?;
+        let (role, expires) = payload.split_once('.')?;
+        if expires.parse::<u64>().ok()? <= now || signature.len() % 2 != 0 {
+            return None;
+        }
+
+        let signature = (0..signature.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        self.signature(payload).verify_slice(&signature).ok()?;
+        role.parse().ok()
+    }
+}
+
+/// Role required for request, `None` for public endpoints.
+fn required_role(method: &Method, path: &str) -> Option<Role> {
+    match (method, path) {
+        (_, "/healthz" | "/readyz") => None,
+        (_, path) if path.starts_with("/auth/") => None,
+        (_, "/admin/config" | "/admin/snapshot") => Some(Role::Admin),
+        (_, path) if path.starts_with("/admin/webhooks") => Some(Role::Admin),
+        (_, path) if path.starts_with("/admin/") => Some(Role::Moderator),
+        (&Method::DELETE, "/snake/votes") => Some(Role::Moderator),
+        (&Method::GET, _) => Some(Role::Viewer),
+        _ => Some(Role::Voter),
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+#[derive(serde::Serialize)]
+struct AuthRejected {
+    error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required_role: Option<Role>,
+}
+
+fn rejected(status: StatusCode, error: &'static str, required_role: Option<Role>) -> Response {
+    let body = AuthRejected {
+        error,
+        required_role,
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Role of request with credentials it was granted for.
+#[derive(Debug, Default)]
+struct Identity {
+    role: Option<Role>,
+    /// validated token or session, `None` for anonymous role
+    credentials: Option<String>,
+}
+
+/// Role of request: static token, registered bot token (voter), session cookie
+/// or anonymous role. Unknown bearer token is rejected, invalid session is ignored.
+async fn identify(app: &AppState, headers: &HeaderMap) -> Result<Identity, Response> {
+    if let Some(token) = bearer_token(headers) {
+        let role = match app.auth.token_role(token) {
+            Some(role) => role,
+            None if app.bots.lock().await.get(token).is_some() => Role::Voter,
+            None => return Err(rejected(StatusCode::UNAUTHORIZED, "invalid token", None)),
+        };
+        return Ok(Identity {
+            role: Some(role),
+            credentials: Some(token.to_owned()),
+        });
+    }
+
+    let session = session_cookie(headers)
+        .and_then(|session| Some((app.auth.session_role(session, now())?, session)));
+    Ok(match session {
+        Some((role, session)) => Identity {
+            role: Some(role),
+            credentials: Some(session.to_owned()),
+        },
+        None => Identity {
+            role: app.auth.anonymous,
+            credentials: None,
+        },
+    })
+}
+
+/// Middleware checking role required by requested endpoint, role and client
+/// of authorized request are available to handlers as extensions.
+pub async fn authorize<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let app = match request.extensions().get::<Arc<AppState>>().cloned() {
+        Some(app) => app,
+        None => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let required = required_role(request.method(), request.uri().path());
+
+    let identity = match identify(&app, request.headers()).await {
+        Ok(identity) => identity,
+        // public endpoints ignore invalid credentials
+        Err(_) if required.is_none() => Identity::default(),
+        Err(response) => return response,
+    };
+    let role = identity.role;
+    match (required, role) {
+        (Some(required), None) => {
+            return rejected(
+                StatusCode::UNAUTHORIZED,
+                "authentication required",
+                Some(required),
+            )
+        }
+        (Some(required), Some(role)) if role < required => {
+            return rejected(StatusCode::FORBIDDEN, "insufficient role", Some(required))
+        }
+        _ => (),
+    }
+
+    if let Some(role) = role {
+        request.extensions_mut().insert(role);
+    }
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<PeerAddr>>()
+        .map_or(PeerAddr(None), |ConnectInfo(peer)| *peer);
+    // only validated credentials tell clients apart, others could be made up on each request
+    let client = Client::identify(identity.credentials.as_deref(), request.headers(), peer);
+    request.extensions_mut().insert(client);
+    next.run(request).await
+}
+
+#[derive(serde::Serialize)]
+struct SessionView {
+    role: Option<Role>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+}
+
+/// Exchange static token (bearer) for session cookie.
+pub async fn handle_login(
+    Extension(app): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let role = match bearer_token(&headers).and_then(|t| app.auth.token_role(t)) {
+        Some(role) => role,
+        None => return rejected(StatusCode::UNAUTHORIZED, "invalid token", None),
+    };
+
+    let expires = now() + SESSION_TTL.as_secs();
+    let cookie = app
+        .auth
+        .session_cookie(&app.auth.session(role, expires), SESSION_TTL.as_secs());
+    let session = SessionView {
+        role: Some(role),
+        expires: Some(expires),
+    };
+    ([(header::SET_COOKIE, cookie)], Json(session)).into_response()
+}
+
+pub async fn handle_logout(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let cookie = app.auth.session_cookie("", 0);
+    ([(header::SET_COOKIE, cookie)], StatusCode::NO_CONTENT)
+}
+
+/// Role of current request.
+pub async fn handle_whoami(role: Option<Extension<Role>>) -> impl IntoResponse {
+    Json(SessionView {
+        role: role.map(|Extension(role)| role),
+        expires: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{identify, required_role, Auth, Role};
+    use axum::http::{header, HeaderMap, Method};
+
+    #[tokio::test]
+    async fn only_valid_credentials_identify_client() {
+        let app = crate::AppState::default();
+        let session = app.auth.session(Role::Moderator, super::now() + 60);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "snapi_session=forged".parse().unwrap());
+        let forged = identify(&app, &headers).await.unwrap();
+        assert_eq!(forged.role, Some(Role::Voter));
+        assert_eq!(forged.credentials, None);
+
+        let cookie = format!("snapi_session={session}");
+        headers.insert(header::COOKIE, cookie.parse().unwrap());
+        let valid = identify(&app, &headers).await.unwrap();
+        assert_eq!(valid.role, Some(Role::Moderator));
+        assert_eq!(valid.credentials, Some(session));
+    }
+
+    #[test]
+    fn session_is_signed_and_expires() {
+        let auth = Auth::default();
+        let session = auth.session(Role::Moderator, 100);
+
+        assert_eq!(auth.session_role(&session, 99), Some(Role::Moderator));
+        assert_eq!(auth.session_role(&session, 100), None);
+
+        let forged = session.replacen("moderator", "admin", 1);
+        assert_eq!(auth.session_role(&forged, 99), None);
+        assert_eq!(Auth::default().session_role(&session, 99), None);
+        assert_eq!(auth.session_role("admin.200.zz", 99), None);
+    }
+
+    #[tokio::test]
+    async fn session_cookie_is_secure_over_tls() {
+        use axum::Extension;
+        use std::sync::Arc;
+
+        let login = |secure: bool| {
+            let mut auth = Auth {
+                tokens: Auth::parse_tokens("moderator:mod1").unwrap(),
+                ..Auth::default()
+            };
+            auth.set_secure_cookie(secure);
+            let app = Arc::new(crate::AppState {
+                auth,
+                ..Default::default()
+            });
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, "Bearer mod1".parse().unwrap());
+            super::handle_login(Extension(app), headers)
+        };
+
+        let response = login(false).await;
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("snapi_session=moderator."));
+        assert!(!cookie.contains("Secure"));
+
+        let response = login(true).await;
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.ends_with("; HttpOnly; SameSite=Strict; Secure"));
+    }
+
+    #[test]
+    fn tokens_are_parsed_with_roles() {
+        let tokens = Auth::parse_tokens("admin:secret, moderator:mod1,").unwrap();
+        assert_eq!(tokens.get("secret"), Some(&Role::Admin));
+        assert_eq!(tokens.get("mod1"), Some(&Role::Moderator));
+
+        let auth = Auth {
+            tokens,
+            ..Auth::default()
+        };
+        assert_eq!(auth.token_role("mod1"), Some(Role::Moderator));
+        assert_eq!(auth.token_role("mod"), None);
+        assert_eq!(auth.token_role("mod2"), None);
+
+        assert!(Auth::parse_tokens("root:secret").is_err());
+        assert!(Auth::parse_tokens("secret").is_err());
+        assert!(Auth::parse_tokens("voter:").is_err());
+    }
+
+    #[test]
+    fn endpoints_require_roles() {
+        assert_eq!(required_role(&Method::GET, "/healthz"), None);
+        assert_eq!(required_role(&Method::GET, "/snake"), Some(Role::Viewer));
+        assert_eq!(required_role(&Method::POST, "/snake/up"), Some(Role::Voter));
+        assert_eq!(
+            required_role(&Method::DELETE, "/snake/votes"),
+            Some(Role::Moderator)
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/admin/pause"),
+            Some(Role::Moderator)
+        );
+        assert_eq!(
+            required_role(&Method::GET, "/admin/config"),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&Method::DELETE, "/admin/webhooks/1"),
+            Some(Role::Admin)
+        );
+        assert!(Role::Admin > Role::Moderator && Role::Voter > Role::Viewer);
+    }
+}
@@ -0,0 +1,575 @@
+use crate::{auth::Role, presence::Client, votes::Vote, AppState};
+use crate::{BOT_MOVE_DEADLINE, INPUT_BUFFER_DEPTH};
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use snapi::snake_game::MovementDirection;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const TOKEN_LENGTH: usize = 32;
+/// max number of registered bots
+const MAX_BOTS: usize = 64;
+/// bot not asking for state nor moving for this long is unregistered
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+/// max time bot waits for state of next tick
+const STATE_WAIT: Duration = Duration::from_secs(5);
+
+pub struct Bot {
+    pub name: String,
+    /// last request of bot
+    seen: Instant,
+}
+
+/// How bots take part in the game, set for the server.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Driving {
+    /// bots vote together with the crowd
+    #[default]
+    Vote,
+    /// bot registered as exclusive by moderator drives the snake alone
+    Exclusive,
+}
+
+impl Driving {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vote => "vote",
+            Self::Exclusive => "exclusive",
+        }
+    }
+}
+
+impl std::str::FromStr for Driving {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vote" => Ok(Self::Vote),
+            "exclusive" => Ok(Self::Exclusive),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Bot driving the snake exclusively.
+struct Driver {
+    token: String,
+    /// turns answered for current tick
+    moves: Vec<MovementDirection>,
+}
+
+#[derive(Default)]
+pub struct Bots {
+    /// registered bots by token
+    registered: HashMap<String, Bot>,
+    driver: Option<Driver>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum BotError {
+    NameTaken,
+    DriverTaken,
+    TooManyBots,
+    /// server does not allow exclusive driving
+    ExclusiveDisabled,
+    /// exclusive driving requires moderator role
+    ExclusiveForbidden,
+    NotDriving,
+    UnknownToken,
+    Opposite,
+    TooManyMoves,
+}
+
+impl Bots {
+    /// Register bot, returns bot token.
+    pub fn register(&mut self, name: &str, exclusive: bool) -> Result<String, BotError> {
+        use rand::{distributions::Alphanumeric, Rng};
+
+        self.expire(Instant::now());
+        if self.registered.values().any(|b| b.name == name) {
+            return Err(BotError::NameTaken);
+        }
+        if self.registered.len() >= MAX_BOTS {
+            return Err(BotError::TooManyBots);
+        }
+        if exclusive && self.driver.is_some() {
+            return Err(BotError::DriverTaken);
+        }
+
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect::<String>();
+
+        if exclusive {
+            self.driver = Some(Driver {
+                token: token.to_owned(),
+                moves: Vec::new(),
+            });
+        }
+        self.registered.insert(
+            token.to_owned(),
+            Bot {
+                name: name.to_owned(),
+                seen: Instant::now(),
+            },
+        );
+
+        Ok(token)
+    }
+
+    pub fn unregister(&mut self, token: &str) -> Result<Bot, BotError> {
+        let bot = self
+            .registered
+            .remove(token)
+            .ok_or(BotError::UnknownToken)?;
+        if self.is_driver(token) {
+            self.driver = None;
+        }
+        Ok(bot)
+    }
+
+    pub fn get(&self, token: &str) -> Option<&Bot> {
+        self.registered.get(token)
+    }
+
+    /// Bot with given token made a request, keeps it from expiring.
+    pub fn seen(&mut self, token: &str, now: Instant) -> Option<&Bot> {
+        let bot = self.registered.get_mut(token)?;
+        bot.seen = now;
+        Some(bot)
+    }
+
+    /// Unregister bots idle for longer than `IDLE_TIMEOUT`, releasing exclusive driving.
+    pub fn expire(&mut self, now: Instant) {
+        self.registered
+            .retain(|_, bot| now.duration_since(bot.seen) < IDLE_TIMEOUT);
+        if matches!(&self.driver, Some(d) if !self.registered.contains_key(&d.token)) {
+            self.driver = None;
+        }
+    }
+
+    /// Unregister bot by its name (bot token is no longer valid).
+    pub fn kick(&mut self, name: &str) -> Result<Bot, BotError> {
+        let token = self
+            .registered
+            .iter()
+            .find(|(_, bot)| bot.name == name)
+            .map(|(token, _)| token.to_owned())
+            .ok_or(BotError::UnknownToken)?;
+        self.unregister(&token)
+    }
+
+    /// Names of registered bots.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .registered
+            .values()
+            .map(|b| b.name.to_owned())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn is_driver(&self, token: &str) -> bool {
+        matches!(&self.driver, Some(d) if d.token == token)
+    }
+
+    /// Name of bot driving the snake.
+    pub fn driver_name(&self) -> Option<&str> {
+        self.driver
+            .as_ref()
+            .and_then(|d| self.registered.get(&d.token))
+            .map(|b| b.name.as_str())
+    }
+
+    /// Queue driving bot turn, validated against last queued direction and space left in
+    /// game input buffer, so every accepted turn is applied.
+    pub fn queue_move(
+        &mut self,
+        token: &str,
+        mov: MovementDirection,
+        queued_direction: MovementDirection,
+        queued_turns: usize,
+    ) -> Result<(), BotError> {
+        if self.get(token).is_none() {
+            return Err(BotError::UnknownToken);
+        }
+        match &mut self.driver {
+            Some(driver) if driver.token == token => {
+                let last_direction = *driver.moves.last().unwrap_or(&queued_direction);
+                if last_direction.is_opposite_to(&mov) {
+                    return Err(BotError::Opposite);
+                }
+                // keeping current direction
+                if last_direction == mov {
+                    return Ok(());
+                }
+                if queued_turns + driver.moves.len() >= INPUT_BUFFER_DEPTH {
+                    return Err(BotError::TooManyMoves);
+                }
+                driver.moves.push(mov);
+                Ok(())
+            }
+            _ => Err(BotError::NotDriving),
+        }
+    }
+
+    /// Take driving bot turns answered for current tick.
+    /// Returns `None` when snake is not driven by bot.
+    pub fn take_driver_moves(&mut self) -> Option<Vec<MovementDirection>> {
+        self.expire(Instant::now());
+        self.driver.as_mut().map(|d| std::mem::take(&mut d.moves))
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct RegisterBot {
+    name: String,
+    #[serde(default)]
+    exclusive: bool,
+}
+
+#[derive(serde::Serialize)]
+struct BotRegistered {
+    name: String,
+    token: String,
+    exclusive: bool,
+}
+
+#[derive(serde::Serialize)]
+struct BotRejected {
+    error: &'static str,
+}
+
+fn rejected(status: StatusCode, error: &'static str) -> Response {
+    (status, Json(BotRejected { error })).into_response()
+}
+
+impl IntoResponse for BotError {
+    fn into_response(self) -> Response {
+        match self {
+            BotError::NameTaken => rejected(StatusCode::CONFLICT, "bot name is taken"),
+            BotError::TooManyBots => {
+                rejected(StatusCode::SERVICE_UNAVAILABLE, "too many bots registered")
+            }
+            BotError::DriverTaken => {
+                rejected(StatusCode::CONFLICT, "game is already driven by other bot")
+            }
+            BotError::ExclusiveDisabled => {
+                rejected(StatusCode::FORBIDDEN, "exclusive driving is disabled")
+            }
+            BotError::ExclusiveForbidden => rejected(
+                StatusCode::FORBIDDEN,
+                "exclusive driving requires moderator role",
+            ),
+            BotError::NotDriving => rejected(StatusCode::CONFLICT, "bot is not driving the game"),
+            BotError::UnknownToken => rejected(StatusCode::UNAUTHORIZED, "unknown bot token"),
+            BotError::Opposite => rejected(
+                StatusCode::CONFLICT,
+                "direction opposite to last queued direction",
+            ),
+            BotError::TooManyMoves => rejected(StatusCode::CONFLICT, "input buffer is full"),
+        }
+    }
+}
+
+fn bot_token(headers: &HeaderMap) -> Result<&str, BotError> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(BotError::UnknownToken)
+}
+
+/// Register bot, exclusive one only when allowed by server and registered by moderator.
+pub async fn handle_register(
+    Extension(app): Extension<Arc<AppState>>,
+    role: Option<Extension<Role>>,
+    Json(request): Json<RegisterBot>,
+) -> Result<impl IntoResponse, BotError> {
+    if request.exclusive {
+        if app.bot_driving != Driving::Exclusive {
+            return Err(BotError::ExclusiveDisabled);
+        }
+        if role.map(|Extension(role)| role) < Some(Role::Moderator) {
+            return Err(BotError::ExclusiveForbidden);
+        }
+    }
+    let token = app
+        .bots
+        .lock()
+        .await
+        .register(&request.name, request.exclusive)?;
+    println!("Bot registered: {}", request.name);
+
+    let registered = BotRegistered {
+        name: request.name,
+        token,
+        exclusive: request.exclusive,
+    };
+    Ok((StatusCode::CREATED, Json(registered)))
+}
+
+pub async fn handle_unregister(
+    Extension(app): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, BotError> {
+    let bot = app.bots.lock().await.unregister(bot_token(&headers)?)?;
+    println!("Bot unregistered: {}", bot.name);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Wait for state of next tick.
+pub async fn handle_state(
+    Extension(app): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, BotError> {
+    let token = bot_token(&headers)?;
+    if app.bots.lock().await.seen(token, Instant::now()).is_none() {
+        return Err(BotError::UnknownToken);
+    }
+
+    // new receiver has seen current state, so it waits for the next one
+    let mut state_recv = app.state_send.subscribe();
+    // on timeout respond with current state
+    tokio::time::timeout(STATE_WAIT, state_recv.changed())
+        .await
+        .ok();
+    let state = state_recv.borrow().clone();
+
+    Ok(Json(state))
+}
+
+/// Queue turn of driving bot, other bots vote with the crowd.
+pub async fn handle_move(
+    Extension(app): Extension<Arc<AppState>>,
+    Extension(client): Extension<Client>,
+    headers: HeaderMap,
+    Path(direction_command): Path<String>,
+) -> Response {
+    let token = match bot_token(&headers) {
+        Ok(token) => token,
+        Err(err) => return err.into_response(),
+    };
+    let mov = match crate::parse_direction(&direction_command, app.topology.as_ref()) {
+        Ok(mov) => mov,
+        Err(error) => return rejected(StatusCode::BAD_REQUEST, error),
+    };
+
+    let driving = {
+        let mut bots = app.bots.lock().await;
+        if bots.seen(token, Instant::now()).is_none() {
+            return BotError::UnknownToken.into_response();
+        }
+        bots.is_driver(token)
+    };
+    if !driving {
+        return crate::accept_vote(&app, &client, Vote::Absolute(mov), direction_command).await;
+    }
+
+    let (published, queued_direction, queued_turns) = {
+        let status = app.game_status.lock().await;
+        (
+            status.published,
+            status.queued_direction,
+            status.queued_turns,
+        )
+    };
+    if published.elapsed() > BOT_MOVE_DEADLINE {
+        return rejected(StatusCode::CONFLICT, "move deadline exceeded");
+    }
+
+    match app
+        .bots
+        .lock()
+        .await
+        .queue_move(token, mov, queued_direction, queued_turns)
+    {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BotError, Bots, RegisterBot};
+    use crate::{auth::Role, presence::Client, AppState};
+    use axum::{
+        extract::Path,
+        http::{header, HeaderMap, StatusCode},
+        response::IntoResponse,
+        Extension, Json,
+    };
+    use snapi::snake_game::MovementDirection::*;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn exclusive_driver() {
+        let mut bots = Bots::default();
+        let driver = bots.register("alpha", true).unwrap();
+        let other = bots.register("beta", false).unwrap();
+
+        assert_eq!(bots.register("alpha", false), Err(BotError::NameTaken));
+        assert_eq!(bots.register("gamma", true), Err(BotError::DriverTaken));
+        assert_eq!(bots.driver_name(), Some("alpha"));
+
+        assert_eq!(
+            bots.queue_move(&other, Up, Right, 0),
+            Err(BotError::NotDriving)
+        );
+        assert_eq!(bots.take_driver_moves(), Some(vec![]));
+        bots.queue_move(&driver, Up, Right, 0).unwrap();
+        bots.queue_move(&driver, Up, Right, 0).unwrap();
+        bots.queue_move(&driver, Left, Right, 0).unwrap();
+        assert_eq!(
+            bots.queue_move(&driver, Right, Right, 0),
+            Err(BotError::Opposite)
+        );
+        assert_eq!(bots.take_driver_moves(), Some(vec![Up, Left]));
+        // turns left in game input buffer
+        bots.queue_move(&driver, Up, Left, 2).unwrap();
+        assert_eq!(
+            bots.queue_move(&driver, Right, Left, 2),
+            Err(BotError::TooManyMoves)
+        );
+        assert_eq!(bots.take_driver_moves(), Some(vec![Up]));
+
+        bots.unregister(&driver).unwrap();
+        assert_eq!(bots.driver_name(), None);
+        assert_eq!(bots.take_driver_moves(), None);
+
+        assert_eq!(bots.names(), vec!["beta"]);
+        bots.kick("beta").unwrap();
+        assert!(bots.get(&other).is_none());
+        assert!(bots.kick("beta").is_err());
+    }
+
+    #[test]
+    fn registrations_are_capped_and_idle_bots_expire() {
+        let mut bots = Bots::default();
+        let driver = bots.register("driver", true).unwrap();
+        for number in 1..super::MAX_BOTS {
+            bots.register(&format!("bot-{number}"), false).unwrap();
+        }
+        assert_eq!(bots.register("late", false), Err(BotError::TooManyBots));
+
+        let later = Instant::now() + super::IDLE_TIMEOUT;
+        bots.seen(&driver, later).unwrap();
+        bots.expire(later);
+        assert_eq!(bots.names(), vec!["driver"]);
+        assert_eq!(bots.driver_name(), Some("driver"));
+
+        bots.expire(later + super::IDLE_TIMEOUT);
+        assert_eq!(bots.driver_name(), None);
+        assert!(bots.get(&driver).is_none());
+    }
+
+    #[tokio::test]
+    async fn bots_vote_unless_exclusive_driving_is_allowed() {
+        let app = Arc::new(AppState::default());
+        let register = |exclusive, role| {
+            let request = RegisterBot {
+                name: "alpha".to_owned(),
+                exclusive,
+            };
+            super::handle_register(
+                Extension(Arc::clone(&app)),
+                Some(Extension(role)),
+                Json(request),
+            )
+        };
+        let status = |response: Result<_, BotError>| response.into_response().status();
+
+        // server setting
+        assert_eq!(
+            status(register(true, Role::Admin).await),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(register(false, Role::Voter).await),
+            StatusCode::CREATED
+        );
+
+        let token = app
+            .bots
+            .lock()
+            .await
+            .registered
+            .keys()
+            .next()
+            .cloned()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        let response = super::handle_move(
+            Extension(Arc::clone(&app)),
+            Extension(Client::from_addr([192, 0, 2, 1].into())),
+            headers,
+            Path("up".to_owned()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(app.selected_moves.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn exclusive_driving_requires_moderator() {
+        let app = Arc::new(AppState {
+            bot_driving: super::Driving::Exclusive,
+            ..Default::default()
+        });
+        let register = |role| {
+            let request = RegisterBot {
+                name: format!("{role:?}"),
+                exclusive: true,
+            };
+            super::handle_register(
+                Extension(Arc::clone(&app)),
+                Some(Extension(role)),
+                Json(request),
+            )
+        };
+
+        let voter = register(Role::Voter).await.into_response();
+        assert_eq!(voter.status(), StatusCode::FORBIDDEN);
+        let moderator = register(Role::Moderator).await.into_response();
+        assert_eq!(moderator.status(), StatusCode::CREATED);
+        assert_eq!(app.bots.lock().await.driver_name(), Some("Moderator"));
+    }
+
+    #[tokio::test]
+    async fn state_request_waits_for_next_tick() {
+        let app = Arc::new(AppState::default());
+        let token = app.bots.lock().await.register("alpha", false).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        let state = || super::handle_state(Extension(Arc::clone(&app)), headers.clone());
+
+        // state of current tick was already published
+        app.state_send.send_replace(None);
+        let waiting = tokio::time::timeout(Duration::from_millis(100), state()).await;
+        assert!(waiting.is_err());
+
+        let next = tokio::spawn(state());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!next.is_finished());
+        app.state_send.send_replace(None);
+        let response = tokio::time::timeout(Duration::from_secs(1), next).await;
+        assert!(response.unwrap().unwrap().is_ok());
+    }
+}
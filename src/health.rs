@@ -0,0 +1,112 @@
+use crate::{AppState, FRAME_TIME};
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// game loop is not ready when it did not tick within given number of frames
+const MAX_MISSED_FRAMES: u32 = 5;
+/// max number of rendered frames waiting for terminal preview
+const MAX_PREVIEW_BACKLOG: usize = 10;
+
+/// Liveness of game and preview threads.
+pub struct Health {
+    start: Instant,
+    /// time of last game tick, in millis since start
+    last_tick: AtomicU64,
+    preview_backlog: AtomicUsize,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            last_tick: AtomicU64::new(0),
+            preview_backlog: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Health {
+    /// Game loop heartbeat.
+    pub fn tick(&self) {
+        let now = self.start.elapsed().as_millis() as u64;
+        self.last_tick.store(now, Ordering::Relaxed);
+    }
+
+    pub fn since_last_tick(&self) -> Duration {
+        let last_tick = Duration::from_millis(self.last_tick.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last_tick)
+    }
+
+    pub fn preview_sent(&self) {
+        self.preview_backlog.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn preview_rendered(&self) {
+        self.preview_backlog.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Frame counted with `preview_sent` was not delivered to preview.
+    pub fn preview_dropped(&self) {
+        self.preview_backlog.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn preview_backlog(&self) -> usize {
+        self.preview_backlog.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.since_last_tick() <= FRAME_TIME * MAX_MISSED_FRAMES
+            && self.preview_backlog() <= MAX_PREVIEW_BACKLOG
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Readiness {
+    ready: bool,
+    since_last_tick_ms: u64,
+    preview_backlog: usize,
+}
+
+/// Process is up.
+pub async fn handle_healthz() -> impl IntoResponse {
+    "ok"
+}
+
+/// Game loop is ticking and preview keeps up with it.
+pub async fn handle_readyz(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let health = &app.health;
+    let readiness = Readiness {
+        ready: health.is_ready(),
+        since_last_tick_ms: health.since_last_tick().as_millis() as u64,
+        preview_backlog: health.preview_backlog(),
+    };
+
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Health, MAX_PREVIEW_BACKLOG};
+
+    #[test]
+    fn blocked_preview_is_not_ready() {
+        let health = Health::default();
+        health.tick();
+        assert!(health.is_ready());
+
+        for _ in 0..=MAX_PREVIEW_BACKLOG {
+            health.preview_sent();
+        }
+        assert!(!health.is_ready());
+
+        health.preview_rendered();
+        assert!(health.is_ready());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{presence::Client, votes::Vote, AppState, GameEvent};
+use snapi::snake_game::{topology::Topology, Turn};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+
+/// wait before connecting again after connection was lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+/// min time between notices about rejected votes, keeps bridge under server flood limits
+const NOTICE_INTERVAL: Duration = Duration::from_secs(2);
+/// min time between notices to the same user
+const USER_NOTICE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// IRC connection settings.
+pub struct IrcConfig {
+    /// `host:port` of IRC server
+    server: String,
+    channel: String,
+    nick: String,
+    password: Option<String>,
+}
+
+impl IrcConfig {
+    /// Settings from `SNAPI_IRC_SERVER`, `SNAPI_IRC_CHANNEL`, `SNAPI_IRC_NICK`
+    /// and `SNAPI_IRC_PASSWORD` env variables, `None` when server is not set.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let server = match std::env::var("SNAPI_IRC_SERVER") {
+            Ok(server) => server,
+            Err(_) => return Ok(None),
+        };
+        let channel = std::env::var("SNAPI_IRC_CHANNEL")
+            .map_err(|_| "SNAPI_IRC_CHANNEL must be set with SNAPI_IRC_SERVER".to_owned())?;
+        if !channel.starts_with('#') {
+            return Err(format!("Invalid IRC channel: {channel}"));
+        }
+
+        Ok(Some(Self {
+            server,
+            channel,
+            nick: std::env::var("SNAPI_IRC_NICK").unwrap_or_else(|_| "snapi".to_owned()),
+            password: std::env::var("SNAPI_IRC_PASSWORD").ok(),
+        }))
+    }
+}
+
+/// Keep bridge connected to IRC server, reconnecting when connection is lost.
+pub fn spawn(app: Arc<AppState>, config: IrcConfig) {
+    tokio::spawn(async move {
+        loop {
+            match session(&app, &config).await {
+                Ok(()) => eprintln!("IRC server {} closed connection.", config.server),
+                Err(err) => eprintln!("IRC connection to {} failed: {err}", config.server),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+/// Single connection: register, join channel, vote with channel commands
+/// and announce game events.
+async fn session(app: &AppState, config: &IrcConfig) -> io::Result<()> {
+    let mut events = app.events.subscribe();
+    let (reader, mut writer) = TcpStream::connect(&config.server).await?.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut nick = config.nick.clone();
+    let mut notices = NoticeLimit::default();
+
+    if let Some(password) = &config.password {
+        send(&mut writer, &format!("PASS {password}")).await?;
+    }
+    send(&mut writer, &format!("NICK {nick}")).await?;
+    send(&mut writer, &format!("USER {nick} 0 * :Snapi crowd voting")).await?;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                let Some(message) = Message::parse(&line) else {
+                    continue;
+                };
+                match (message.command, message.params.as_slice()) {
+                    ("PING", [token, ..]) => {
+                        send(&mut writer, &format!("PONG :{token}")).await?;
+                    }
+                    // registered
+                    ("001", _) => {
+                        println!("IRC bridge connected to {} as {nick}.", config.server);
+                        send(&mut writer, &format!("JOIN {}", config.channel)).await?;
+                    }
+                    // nick in use
+                    ("433", _) => {
+                        nick.push('_');
+                        send(&mut writer, &format!("NICK {nick}")).await?;
+                    }
+                    ("PRIVMSG", [target, text])
+                        if target.eq_ignore_ascii_case(&config.channel) =>
+                    {
+                        let Some(sender) = message.nick() else {
+                            continue;
+                        };
+                        let client = Client::chat(message.host().unwrap_or(sender));
+                        let Some(notice) = vote(app, &client, text).await else {
+                            continue;
+                        };
+                        if notices.allow(sender, Instant::now()) {
+                            send(&mut writer, &format!("NOTICE {sender} :{notice}")).await?;
+                        }
+                    }
+                    ("ERROR", params) => {
+                        let reason = params.first().unwrap_or(&"").to_string();
+                        return Err(io::Error::new(io::ErrorKind::ConnectionAborted, reason));
+                    }
+                    _ => (),
+                }
+            }
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return Ok(()),
+                };
+                if let Some(text) = announcement(&event) {
+                    send(&mut writer, &format!("PRIVMSG {} :{text}", config.channel)).await?;
+                }
+            }
+        }
+    }
+}
+
+async fn send<W: AsyncWrite + Unpin>(writer: &mut W, line: &str) -> io::Result<()> {
+    // line breaks would start new commands
+    let line = line.replace(['\r', '\n'], " ");
+    writer.write_all(format!("{line}\r\n").as_bytes()).await
+}
+
+/// Rate limit of notices about rejected votes, other rejections are not answered.
+#[derive(Default)]
+struct NoticeLimit {
+    last: Option<Instant>,
+    /// last notice by nick
+    users: HashMap<String, Instant>,
+}
+
+impl NoticeLimit {
+    fn allow(&mut self, nick: &str, now: Instant) -> bool {
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < NOTICE_INTERVAL)
+        {
+            return false;
+        }
+        self.users
+            .retain(|_, last| now.duration_since(*last) < USER_NOTICE_INTERVAL);
+        if self.users.contains_key(nick) {
+            return false;
+        }
+
+        self.last = Some(now);
+        self.users.insert(nick.to_owned(), now);
+        true
+    }
+}
+
+/// Submit vote from chat command of chat user, rejection reason is returned for notice.
+async fn vote(app: &AppState, client: &Client, text: &str) -> Option<String> {
+    let (vote, command) = match parse_command(text, app.topology.as_ref())? {
+        Ok(parsed) => parsed,
+        Err(error) => return Some(format!("{text}: {error}")),
+    };
+    match crate::submit_vote(app, client, vote, command.to_owned()).await {
+        Ok(_) => None,
+        Err((_, rejected)) => Some(format!("{text}: {}", rejected.error)),
+    }
+}
+
+/// Vote of chat command (`!up`, `!straight`, `!turn left`) with command name,
+/// `None` for other messages and unknown commands.
+fn parse_command<'a>(
+    text: &'a str,
+    topology: &dyn Topology,
+) -> Option<Result<(Vote, &'a str), &'static str>> {
+    let mut words = text.strip_prefix('!')?.split_whitespace();
+    let command = words.next()?;
+    let vote = match (command, words.next()) {
+        ("straight", None) => Ok(Vote::Relative(Turn::Straight)),
+        ("turn", Some("left")) => Ok(Vote::Relative(Turn::Left)),
+        ("turn", Some("right")) => Ok(Vote::Relative(Turn::Right)),
+        ("turn", _) => Err("unknown turn"),
+        (direction, None) => match crate::parse_direction(direction, topology) {
+            // not a vote, may be a command for another bot
+            Err("unknown direction") => return None,
+            result => result.map(Vote::Absolute),
+        },
+        _ => return None,
+    };
+
+    Some(vote.map(|vote| (vote, command)))
+}
+
+/// Channel message for game event, `None` for events not announced in chat.
+fn announcement(event: &GameEvent) -> Option<String> {
+    match event {
+        GameEvent::GameOver { game, score } => {
+            let result = match (score.won, &score.puzzle, &score.death) {
+                (true, Some(puzzle), _) => format!("puzzle {puzzle} solved"),
+                (true, None, _) => "won".to_owned(),
+                (false, _, Some(death)) => death.name().replace('_', " "),
+                (false, _, None) => "stopped".to_owned(),
+            };
+            Some(format!(
+                "Game {game} over ({result}): snake length {} after {} ticks",
+                score.length, score.ticks
+            ))
+        }
+        GameEvent::HighScore {
+            length, previous, ..
+        } => Some(format!(
+            "New high score: snake length {length} (previous best {previous})"
+        )),
+        GameEvent::GameStarted { .. } | GameEvent::Milestone { .. } => None,
+    }
+}
+
+/// IRC protocol message: `[:prefix] COMMAND params [:trailing]`.
+struct Message<'a> {
+    prefix: Option<&'a str>,
+    command: &'a str,
+    params: Vec<&'a str>,
+}
+
+impl<'a> Message<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let (prefix, rest) = match line.strip_prefix(':') {
+            Some(rest) => {
+                let (prefix, rest) = rest.split_once(' ')?;
+                (Some(prefix), rest)
+            }
+            None => (None, line),
+        };
+        let (rest, trailing) = match rest.split_once(" :") {
+            Some((rest, trailing)) => (rest, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = rest.split(' ').filter(|w| !w.is_empty());
+        let command = words.next()?;
+
+        Some(Self {
+            prefix,
+            command,
+            params: words.chain(trailing).collect(),
+        })
+    }
+
+    /// nick of user who sent the message
+    fn nick(&self) -> Option<&'a str> {
+        let prefix = self.prefix?;
+        Some(prefix.split_once('!').map_or(prefix, |(nick, _)| nick))
+    }
+
+    /// host of user who sent the message, unlike nick it is kept on nick change
+    fn host(&self) -> Option<&'a str> {
+        self.prefix?.split_once('@').map(|(_, host)| host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_command, IrcConfig, Message, NoticeLimit};
+    use crate::{scores::GameScore, votes::Vote, AppState, GameEvent};
+    use snapi::snake_game::{topology, MovementDirection, Turn};
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn messages_and_commands_are_parsed() {
+        let message = Message::parse(":alice!a@host PRIVMSG #snapi :!turn left").unwrap();
+        assert_eq!(message.nick(), Some("alice"));
+        assert_eq!(message.host(), Some("host"));
+        assert_eq!(message.command, "PRIVMSG");
+        assert_eq!(message.params, vec!["#snapi", "!turn left"]);
+        assert_eq!(Message::parse("PING :abc").unwrap().params, vec!["abc"]);
+
+        let square = topology::Square;
+        let hex = topology::Hex;
+        assert_eq!(
+            parse_command("!up", &square),
+            Some(Ok((Vote::Absolute(MovementDirection::Up), "up")))
+        );
+        assert_eq!(
+            parse_command("!turn left", &square),
+            Some(Ok((Vote::Relative(Turn::Left), "turn")))
+        );
+        assert!(matches!(parse_command("!up", &hex), Some(Err(_))));
+        assert_eq!(parse_command("!help", &square), None);
+        assert_eq!(parse_command("up", &square), None);
+    }
+
+    #[test]
+    fn rejection_notices_are_rate_limited() {
+        let mut notices = NoticeLimit::default();
+        let start = std::time::Instant::now();
+
+        assert!(notices.allow("alice", start));
+        assert!(!notices.allow("bob", start + super::NOTICE_INTERVAL / 2));
+        assert!(notices.allow("bob", start + super::NOTICE_INTERVAL));
+        assert!(!notices.allow("alice", start + 2 * super::NOTICE_INTERVAL));
+        assert!(notices.allow("alice", start + super::USER_NOTICE_INTERVAL));
+    }
+
+    #[tokio::test]
+    async fn bridge_votes_and_announces_game_over() {
+        let server = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = IrcConfig {
+            server: server.local_addr().unwrap().to_string(),
+            channel: "#snapi".to_owned(),
+            nick: "snapi".to_owned(),
+            password: None,
+        };
+        let app = Arc::new(AppState::default());
+        let bridge_app = Arc::clone(&app);
+        tokio::spawn(async move { super::session(&bridge_app, &config).await });
+
+        // stand-in IRC server
+        let (stream, _) = server.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "NICK snapi");
+        assert!(lines
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("USER "));
+        writer
+            .write_all(b":irc 433 * snapi :in use\r\n")
+            .await
+            .unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "NICK snapi_");
+        writer
+            .write_all(b":irc 001 snapi_ :Welcome\r\nPING :42\r\n")
+            .await
+            .unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "JOIN #snapi");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "PONG :42");
+
+        writer
+            .write_all(b":bob!b@host PRIVMSG #snapi :!up\r\n:bob!b@host PRIVMSG #snapi :!left\r\n")
+            .await
+            .unwrap();
+        // snake moves right, left is rejected
+        assert_eq!(
+            lines.next_line().await.unwrap().unwrap(),
+            "NOTICE bob :!left: direction opposite to snake movement"
+        );
+        assert_eq!(
+            *app.selected_moves.lock().await,
+            vec![Vote::Absolute(MovementDirection::Up)]
+        );
+
+        let score = GameScore {
+            ticks: 120,
+            length: 9,
+            death: Some(snapi::snake_game::DeathCause::ReversedIntoItself),
+            bot: None,
+            won: false,
+            puzzle: None,
+        };
+        app.events.send(GameEvent::GameOver { game: 3, score }).ok();
+        assert_eq!(
+            lines.next_line().await.unwrap().unwrap(),
+            "PRIVMSG #snapi :Game 3 over (reversed into itself): snake length 9 after 120 ticks"
+        );
+    }
+}
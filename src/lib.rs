@@ -0,0 +1,12 @@
+//! Snake game engine used by `snapi` game server.
+//!
+//! Game is set up with [`GameBuilder`], composed of [`GameLevel`] with snake
+//! ([`SnakeBehavior`]) and fruits ([`FruitBehavior`]) policies, and rendered with
+//! [`GameDisplay`] implementations from [`snake_game::renderer`].
+
+pub mod snake_game;
+
+pub use snake_game::{
+    FruitBehavior, Game, GameBuilder, GameDisplay, GameError, GameLevel, MovementDirection,
+    SnakeBehavior, Tile, TileType,
+};
@@ -0,0 +1,430 @@
+use crate::tls::TlsConfig;
+use axum::extract::connect_info::Connected;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+
+/// max time for client to finish TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// accepted connections waiting for server
+const ACCEPT_BACKLOG: usize = 64;
+/// pause after failed accept, e.g. when out of file descriptors
+pub const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+/// first file descriptor passed with systemd socket activation
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Address server listens on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    /// IPv4 or IPv6 address with port
+    Tcp(SocketAddr),
+    /// unix domain socket path, written as `unix:<path>`
+    Unix(PathBuf),
+    /// all sockets passed with systemd socket activation
+    Systemd,
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "systemd" {
+            return Ok(Self::Systemd);
+        }
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("Unix socket path is empty".to_owned());
+            }
+            return Ok(Self::Unix(path.into()));
+        }
+        s.parse()
+            .map(Self::Tcp)
+            .map_err(|_| format!("Invalid listen address: {s}"))
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+            Self::Systemd => write!(f, "systemd"),
+        }
+    }
+}
+
+/// Addresses from `SNAPI_LISTEN` env variable (separated with commas). When not set,
+/// sockets passed by systemd are used, or `default` address without socket activation.
+pub fn addrs_from_env(default: &str, systemd_fds: Option<i32>) -> Result<Vec<ListenAddr>, String> {
+    match std::env::var("SNAPI_LISTEN") {
+        Ok(value) => parse_addrs(&value),
+        Err(_) if systemd_fds.is_some() => Ok(vec![ListenAddr::Systemd]),
+        Err(_) => parse_addrs(default),
+    }
+}
+
+fn parse_addrs(value: &str) -> Result<Vec<ListenAddr>, String> {
+    let addrs = value
+        .split(',')
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if addrs.is_empty() {
+        return Err("No listen address set".to_owned());
+    }
+    Ok(addrs)
+}
+
+/// Remove socket files created for unix socket addresses.
+pub fn remove_unix_sockets(addrs: &[ListenAddr]) {
+    for addr in addrs {
+        if let ListenAddr::Unix(path) = addr {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// Number of sockets systemd passed to this process. Socket activation environment is cleared,
+/// so the sockets are not passed on to child processes. Environment must not be changed while
+/// other threads run, call this before starting async runtime.
+pub fn take_systemd_fds() -> Option<i32> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let count = std::env::var("LISTEN_FDS").ok();
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    let pid: u32 = pid?.parse().ok()?;
+    if pid != std::process::id() {
+        return None;
+    }
+    count?.parse().ok().filter(|count| *count > 0)
+}
+
+/// Socket bound to one of listen addresses.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    /// listener with its path, `None` for unnamed sockets
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+impl Listener {
+    /// Bind all addresses, error names the address that failed.
+    /// `systemd_fds` is number of sockets passed by systemd, see [`take_systemd_fds`].
+    pub async fn bind(
+        addrs: &[ListenAddr],
+        mut systemd_fds: Option<i32>,
+    ) -> Result<Vec<Self>, String> {
+        let mut listeners = Vec::new();
+        for addr in addrs {
+            let bound = match addr {
+                ListenAddr::Tcp(socket) => {
+                    TcpListener::bind(socket).await.map(|l| vec![Self::Tcp(l)])
+                }
+                ListenAddr::Unix(path) => Self::bind_unix(path).map(|l| vec![l]),
+                // sockets can be taken over only once
+                ListenAddr::Systemd => Self::from_systemd(systemd_fds.take()),
+            };
+            listeners.extend(bound.map_err(|err| format!("Cannot listen on {addr}: {err}"))?);
+        }
+        Ok(listeners)
+    }
+
+    pub fn is_tcp(&self) -> bool {
+        matches!(self, Self::Tcp(_))
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: &Path) -> io::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        // socket file left by previous run is replaced, unless a server still answers on it
+        let is_socket =
+            std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+        if is_socket {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(io::ErrorKind::AddrInUse.into());
+            }
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        Ok(Self::Unix(listener, Some(path.to_owned())))
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_path: &Path) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform",
+        ))
+    }
+
+    /// Take over `count` sockets passed by systemd, they can be taken only once.
+    #[cfg(unix)]
+    fn from_systemd(count: Option<i32>) -> io::Result<Vec<Self>> {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+        let count =
+            count.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no sockets passed"))?;
+
+        (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+            .map(|fd| {
+                // SAFETY: systemd passes listening sockets from descriptor 3 on, count is
+                // taken from environment only once so the descriptors are owned only here
+                let tcp = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+                // local address of unix socket is not an IP address
+                if tcp.local_addr().is_ok() {
+                    tcp.set_nonblocking(true)?;
+                    return Ok(Self::Tcp(TcpListener::from_std(tcp)?));
+                }
+
+                // SAFETY: descriptor is released by TCP listener above
+                let unix =
+                    unsafe { std::os::unix::net::UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+                let path = unix.local_addr()?.as_pathname().map(Path::to_owned);
+                unix.set_nonblocking(true)?;
+                Ok(Self::Unix(UnixListener::from_std(unix)?, path))
+            })
+            .collect()
+    }
+
+    #[cfg(not(unix))]
+    fn from_systemd(_count: Option<i32>) -> io::Result<Vec<Self>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "socket activation is not supported on this platform",
+        ))
+    }
+
+    async fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Self::Tcp(listener) => listener.accept().await.map(|(s, _)| Stream::Tcp(s)),
+            #[cfg(unix)]
+            Self::Unix(listener, _) => listener.accept().await.map(|(s, _)| Stream::Unix(s)),
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{addr}"),
+                Err(_) => write!(f, "tcp"),
+            },
+            #[cfg(unix)]
+            Self::Unix(_, Some(path)) => write!(f, "unix:{}", path.display()),
+            #[cfg(unix)]
+            Self::Unix(_, None) => write!(f, "unix"),
+        }
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// Connection accepted on any listener.
+pub trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+/// Connection with address of connected client.
+pub struct Accepted {
+    io: Box<dyn Connection>,
+    peer: Option<IpAddr>,
+}
+
+impl AsyncRead for Accepted {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Accepted {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// IP address of connected client, available to handlers as `ConnectInfo`,
+/// `None` for unix socket connections.
+#[derive(Debug, Copy, Clone)]
+pub struct PeerAddr(pub Option<IpAddr>);
+
+impl Connected<&Accepted> for PeerAddr {
+    fn connect_info(target: &Accepted) -> Self {
+        Self(target.peer)
+    }
+}
+
+/// Connections accepted on all listeners. With TLS configured, TCP connections
+/// finish handshake in background so slow clients do not block other connections,
+/// unix sockets are local and stay plain.
+pub struct Incoming {
+    connections: mpsc::Receiver<Accepted>,
+}
+
+impl Incoming {
+    pub fn new(listeners: Vec<Listener>, tls: Option<Arc<TlsConfig>>) -> Self {
+        let (send, connections) = mpsc::channel(ACCEPT_BACKLOG);
+
+        for listener in listeners {
+            let send = send.clone();
+            let tls = tls.clone().filter(|_| listener.is_tcp());
+            tokio::spawn(async move {
+                loop {
+                    let stream = match listener.accept().await {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            eprintln!("Accepting connection on {listener} failed: {err}");
+                            tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                            continue;
+                        }
+                    };
+
+                    // server is gone
+                    if send.is_closed() {
+                        break;
+                    }
+
+                    let accepted = match (stream, &tls) {
+                        (Stream::Tcp(stream), Some(tls)) => {
+                            let peer = stream.peer_addr().ok().map(|addr| addr.ip());
+                            let acceptor = tls.acceptor();
+                            let connection_send = send.clone();
+                            tokio::spawn(async move {
+                                let handshake = tokio::time::timeout(
+                                    HANDSHAKE_TIMEOUT,
+                                    acceptor.accept(stream),
+                                );
+                                // failed handshakes (plain HTTP, scanners) are dropped
+                                if let Ok(Ok(stream)) = handshake.await {
+                                    let io = Box::new(stream);
+                                    connection_send.send(Accepted { io, peer }).await.ok();
+                                }
+                            });
+                            continue;
+                        }
+                        (Stream::Tcp(stream), None) => Accepted {
+                            peer: stream.peer_addr().ok().map(|addr| addr.ip()),
+                            io: Box::new(stream),
+                        },
+                        #[cfg(unix)]
+                        (Stream::Unix(stream), _) => Accepted {
+                            io: Box::new(stream),
+                            peer: None,
+                        },
+                    };
+                    send.send(accepted).await.ok();
+                }
+            });
+        }
+
+        Self { connections }
+    }
+}
+
+impl hyper::server::accept::Accept for Incoming {
+    type Conn = Accepted;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.connections.poll_recv(cx).map(|c| c.map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_addrs, ListenAddr};
+
+    #[test]
+    fn listen_addresses_are_parsed() {
+        assert_eq!(
+            parse_addrs("0.0.0.0:3000, [::1]:3001,unix:/run/snapi.sock,systemd").unwrap(),
+            vec![
+                ListenAddr::Tcp("0.0.0.0:3000".parse().unwrap()),
+                ListenAddr::Tcp("[::1]:3001".parse().unwrap()),
+                ListenAddr::Unix("/run/snapi.sock".into()),
+                ListenAddr::Systemd,
+            ]
+        );
+        assert!(parse_addrs(" , ").is_err());
+        assert!(parse_addrs("localhost").unwrap_err().contains("localhost"));
+        assert!(parse_addrs("unix:").is_err());
+    }
+
+    #[tokio::test]
+    async fn systemd_sockets_require_activation() {
+        let err = super::Listener::bind(&[ListenAddr::Systemd], None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("no sockets passed"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_socket_accepts_connections_and_replaces_stale_file() {
+        use super::{Incoming, Listener};
+        use hyper::server::accept::Accept;
+
+        let path = std::env::temp_dir().join(format!("snapi-{}.sock", std::process::id()));
+        let addrs = [ListenAddr::Unix(path.clone())];
+        // stale socket of stopped server
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let listeners = Listener::bind(&addrs, None).await.unwrap();
+        assert_eq!(listeners[0].to_string(), format!("unix:{}", path.display()));
+        // taken by running server
+        assert!(Listener::bind(&addrs, None)
+            .await
+            .unwrap_err()
+            .contains("in use"));
+
+        let mut incoming = Incoming::new(listeners, None);
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+        let accepted =
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut incoming).poll_accept(cx)).await;
+        assert!(matches!(accepted, Some(Ok(_))));
+
+        super::remove_unix_sockets(&addrs);
+        assert!(!path.exists());
+    }
+}
@@ -1,70 +1,459 @@
-mod helper;
-mod snake_game;
+mod admin;
+mod auth;
+mod bots;
+mod health;
+mod irc;
+mod listen;
+mod metrics;
+mod presence;
+mod recording;
+mod scores;
+mod simulate;
+mod telnet;
+mod tls;
+mod webhooks;
 
 use axum::{
-    extract::Path,
-    http::{header, StatusCode},
-    response::IntoResponse,
-    routing::{get, post},
-    Extension, Router,
+    body::Bytes,
+    extract::{MatchedPath, Path},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
 };
-use snake_game::MovementDirection;
+use recording::{FrameBuffer, Replay};
+use snapi::snake_game::{
+    self,
+    topology::{self, Boundary, Topology},
+    votes, MovementDirection, Turn,
+};
+use std::collections::HashMap;
 use std::sync::mpsc;
 use std::sync::Arc;
-use tokio::sync::{oneshot, Mutex};
+use std::time::Instant;
+use tokio::sync::{broadcast, oneshot, watch, Mutex};
+use votes::Vote;
 
-const LISTEN_ADDR: &str = "0.0.0.0:3000";
+const LISTEN_ADDR: &str = "0.0.0.0:3000"; // default, comma separated addresses in SNAPI_LISTEN env
 const FRAME_TIME: std::time::Duration = std::time::Duration::from_millis(200);
 const SNAKE_TAIL_SIZE: usize = 2; // snake len = head + tail size
 const MAX_FRUITS: usize = 5;
 const NEW_FRUIT_CHANCE: f64 = 0.1; // 10% on each move
 const LEVEL_WIDTH: usize = 40;
 const LEVEL_HEIGHT: usize = 20;
+const RECENT_FRAMES: usize = 50; // frames kept for recent gameplay gif
+const MAX_REPLAY_FRAMES: usize = 9000; // latest frames kept for finished game replay
+const AI_FALLBACK_TICKS: u64 = 25; // ticks without votes after which AI steers the snake
+const AI_CONTROLLER: &str = "path"; // default, overridden by SNAPI_AI_CONTROLLER env
+const INPUT_BUFFER_DEPTH: usize = 3; // turns queued by bot driving the snake or single player
+const BOT_MOVE_DEADLINE: std::time::Duration = std::time::Duration::from_millis(150);
+const STREAM_BUFFER: usize = 64; // frames buffered for slow stream clients
+const EVENT_BUFFER: usize = 16; // game events buffered for slow integrations
+const LENGTH_MILESTONES: [usize; 4] = [10, 25, 50, 100]; // snake lengths announced as game events
+const GAME_MODE: &str = "endless"; // default, `puzzles` plays built-in puzzles (SNAPI_MODE env)
+const TOPOLOGY: &str = "square"; // default, `square8` or `hex` (SNAPI_TOPOLOGY env)
+const BOUNDARY: &str = "wrap"; // default endless mode level edges (SNAPI_BOUNDARY env)
+const STEERING: &str = "any"; // default, `absolute` or `relative` votes only (SNAPI_STEERING env)
+const VOTE_STRATEGY: &str = "sample"; // default, `quorum[:percent]` (SNAPI_VOTE_STRATEGY env)
+const BOT_DRIVING: &str = "vote"; // default, `exclusive` lets one bot drive (SNAPI_BOT_DRIVING env)
 
-#[derive(Default)]
 struct AppState {
-    level_display: Arc<Mutex<String>>,
-    selected_moves: Arc<Mutex<Vec<MovementDirection>>>,
+    selected_moves: Arc<Mutex<Vec<votes::Vote>>>,
+    last_votes: Arc<Mutex<Option<votes::VoteResult>>>,
+    game_status: Arc<Mutex<GameStatus>>,
+    recent_frames: Arc<Mutex<FrameBuffer>>,
+    last_replay: Arc<Mutex<Arc<Replay>>>,
+    bots: Arc<Mutex<bots::Bots>>,
+    scores: Arc<Mutex<scores::ScoreHistory>>,
+    state_send: watch::Sender<Option<TickState>>,
+    state_recv: watch::Receiver<Option<TickState>>,
+    stream_send: broadcast::Sender<StreamFrame>,
+    events: broadcast::Sender<GameEvent>,
+    metrics: metrics::Metrics,
+    health: health::Health,
+    topology: Arc<dyn Topology>,
+    boundary: Boundary,
+    /// allowed voting style (absolute directions, turns or both)
+    steering: votes::Steering,
+    /// how movement is selected from votes
+    strategy: votes::Strategy,
+    /// whether bots vote or one bot drives alone
+    bot_driving: bots::Driving,
+    /// recently seen viewers and voters
+    presence: Arc<Mutex<presence::Presence>>,
+    auth: auth::Auth,
+    /// game ticks are stopped by moderator
+    paused: Arc<Mutex<bool>>,
+    /// addresses server listens on
+    listeners: Vec<String>,
+    webhooks: Arc<Mutex<webhooks::Webhooks>>,
+}
+
+/// Current game state shared with request handlers.
+struct GameStatus {
+    tick: u64,
+    direction: MovementDirection,
+    /// direction after applying turns waiting in game input buffer
+    queued_direction: MovementDirection,
+    /// turns waiting in game input buffer
+    queued_turns: usize,
+    /// when tick state was published
+    published: Instant,
+}
+
+/// Game state published on every tick.
+#[derive(Clone, serde::Serialize)]
+struct TickState {
+    /// number of game since server start
+    game: u64,
+    tick: u64,
+    /// time for bot to answer with movement
+    deadline_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    puzzle: Option<PuzzleInfo>,
+    /// distinct viewers and active voters
+    audience: presence::Audience,
+    #[serde(flatten)]
+    state: snake_game::renderer::GameState,
+}
+
+/// Tiles changed during game tick.
+#[derive(serde::Serialize)]
+struct TickDelta {
+    game: u64,
+    tick: u64,
+    changes: Vec<snake_game::TileChange>,
+}
+
+/// Frame of game stream, full state at game start followed by deltas.
+#[derive(Clone)]
+enum StreamFrame {
+    State(Arc<TickState>),
+    Delta(Arc<TickDelta>),
+}
+
+/// Game event passed to chat bridge and webhooks.
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum GameEvent {
+    GameStarted {
+        game: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        puzzle: Option<String>,
+    },
+    /// snake grew to one of milestone lengths
+    Milestone { game: u64, tick: u64, length: usize },
+    /// game ended longer than any game since server start
+    HighScore {
+        game: u64,
+        length: usize,
+        previous: usize,
+    },
+    /// game ended with final score
+    GameOver { game: u64, score: scores::GameScore },
+}
+
+impl GameEvent {
+    /// short name, same as `event` field of serialized event
+    fn name(&self) -> &'static str {
+        match self {
+            Self::GameStarted { .. } => "game_started",
+            Self::Milestone { .. } => "milestone",
+            Self::HighScore { .. } => "high_score",
+            Self::GameOver { .. } => "game_over",
+        }
+    }
+}
+
+/// Frame of terminal preview.
+enum PreviewFrame {
+    Full(String),
+    Delta(Vec<snake_game::TileChange>),
+}
+
+/// Puzzle played in puzzles mode.
+#[derive(Clone, serde::Serialize)]
+struct PuzzleInfo {
+    name: String,
+    /// counting from 1
+    number: usize,
+    total: usize,
+}
+
+/// Game loop finished without error.
+enum LoopExit {
+    Shutdown,
+    /// win conditions were met
+    Won,
+    /// snake filled whole level
+    BoardFull,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        let (state_send, state_recv) = watch::channel(None);
+        let (stream_send, _) = broadcast::channel(STREAM_BUFFER);
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            selected_moves: Default::default(),
+            last_votes: Default::default(),
+            game_status: Arc::new(Mutex::new(GameStatus {
+                tick: 0,
+                direction: MovementDirection::Right,
+                queued_direction: MovementDirection::Right,
+                queued_turns: 0,
+                published: Instant::now(),
+            })),
+            recent_frames: Arc::new(Mutex::new(FrameBuffer::new(RECENT_FRAMES))),
+            last_replay: Arc::new(Mutex::new(Arc::new(Replay::new(FrameBuffer::new(0))))),
+            bots: Default::default(),
+            scores: Default::default(),
+            state_send,
+            state_recv,
+            stream_send,
+            events,
+            metrics: Default::default(),
+            health: Default::default(),
+            topology: Arc::new(topology::Square),
+            boundary: Boundary::Wrap,
+            steering: Default::default(),
+            strategy: Default::default(),
+            bot_driving: Default::default(),
+            presence: Default::default(),
+            auth: Default::default(),
+            paused: Default::default(),
+            listeners: Vec::new(),
+            webhooks: Default::default(),
+        }
+    }
+}
+
+/// Level topology and edges from environment, puzzles are played on square grid only.
+fn level_shape(game_mode: &str) -> (Arc<dyn Topology>, Boundary) {
+    let topology_name = std::env::var("SNAPI_TOPOLOGY").unwrap_or_else(|_| TOPOLOGY.to_owned());
+    let topology = match topology::by_name(&topology_name) {
+        Some(_) if game_mode == "puzzles" && topology_name != "square" => {
+            eprintln!("Puzzles are played on square grid, ignoring topology: {topology_name}");
+            Arc::new(topology::Square)
+        }
+        Some(topology) => topology,
+        None => {
+            eprintln!("Unknown topology: {topology_name}");
+            Arc::new(topology::Square)
+        }
+    };
+
+    let boundary_name = std::env::var("SNAPI_BOUNDARY").unwrap_or_else(|_| BOUNDARY.to_owned());
+    let boundary = match boundary_name.parse::<Boundary>() {
+        Ok(boundary) if !boundary.fits(topology.as_ref(), LEVEL_HEIGHT) => {
+            eprintln!(
+                "Level boundary {boundary_name} does not fit {} topology with height {LEVEL_HEIGHT}",
+                topology.name()
+            );
+            Boundary::Wrap
+        }
+        Ok(boundary) => boundary,
+        Err(_) => {
+            eprintln!("Unknown level boundary: {boundary_name}");
+            Boundary::Wrap
+        }
+    };
+
+    (topology, boundary)
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    if let Some("simulate") = args.next().as_deref() {
+        if let Err(err) = simulate::run(args) {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    // environment is changed before runtime starts its threads
+    let systemd_fds = listen::take_systemd_fds();
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("Cannot start async runtime: {err}");
+            std::process::exit(2);
+        }
+    };
+    runtime.block_on(serve(systemd_fds));
 }
 
-#[tokio::main]
-async fn main() {
-    let app_state = Arc::new(AppState::default());
+/// Run game server, `systemd_fds` is number of sockets passed with socket activation.
+async fn serve(systemd_fds: Option<i32>) {
+    let game_mode = std::env::var("SNAPI_MODE").unwrap_or_else(|_| GAME_MODE.to_owned());
+    let (topology, boundary) = level_shape(&game_mode);
+    let steering_name = std::env::var("SNAPI_STEERING").unwrap_or_else(|_| STEERING.to_owned());
+    let steering = steering_name.parse().unwrap_or_else(|_| {
+        eprintln!("Unknown steering: {steering_name}");
+        votes::Steering::Any
+    });
+    let strategy_name =
+        std::env::var("SNAPI_VOTE_STRATEGY").unwrap_or_else(|_| VOTE_STRATEGY.to_owned());
+    let strategy = strategy_name.parse().unwrap_or_else(|_| {
+        eprintln!("Unknown vote strategy: {strategy_name}");
+        votes::Strategy::Sample
+    });
+    let bot_driving_name =
+        std::env::var("SNAPI_BOT_DRIVING").unwrap_or_else(|_| BOT_DRIVING.to_owned());
+    let bot_driving = bot_driving_name.parse().unwrap_or_else(|_| {
+        eprintln!("Unknown bot driving: {bot_driving_name}");
+        bots::Driving::Vote
+    });
+    let tls_config = match tls::TlsConfig::from_env() {
+        Ok(tls_config) => tls_config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let mut auth = match auth::Auth::from_env() {
+        Ok(auth) => auth,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let irc_config = match irc::IrcConfig::from_env() {
+        Ok(irc_config) => irc_config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let webhooks = match webhooks::Webhooks::from_env() {
+        Ok(webhooks) => webhooks,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let telnet_listener = match telnet::addr_from_env() {
+        Ok(Some(addr)) => match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => Some(listener),
+            Err(err) => {
+                eprintln!("Cannot listen on telnet {addr}: {err}");
+                std::process::exit(2);
+            }
+        },
+        Ok(None) => None,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let listen_addrs = match listen::addrs_from_env(LISTEN_ADDR, systemd_fds) {
+        Ok(listen_addrs) => listen_addrs,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let listeners = match listen::Listener::bind(&listen_addrs, systemd_fds).await {
+        Ok(listeners) => listeners,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    auth.set_secure_cookie(tls_config.is_some() && listeners.iter().any(|l| l.is_tcp()));
+    let app_state = Arc::new(AppState {
+        topology,
+        boundary,
+        steering,
+        strategy,
+        bot_driving,
+        auth,
+        listeners: listeners.iter().map(ToString::to_string).collect(),
+        webhooks: Arc::new(Mutex::new(webhooks)),
+        ..Default::default()
+    });
+    webhooks::spawn(Arc::clone(&app_state));
+    if let Some(irc_config) = irc_config {
+        irc::spawn(Arc::clone(&app_state), irc_config);
+    }
+    if let Some(listener) = telnet_listener {
+        if let Ok(addr) = listener.local_addr() {
+            println!("Telnet is running at: {addr}");
+        }
+        telnet::spawn(Arc::clone(&app_state), listener);
+    }
     let (shutdown_sig, shutdown_recv) = oneshot::channel::<()>();
     let (game_exit_sig, game_exit_recv) = mpsc::channel::<()>();
-    let (preview_send, preview_recv) = mpsc::channel::<String>();
+    let (preview_send, preview_recv) = mpsc::channel::<PreviewFrame>();
 
     // game thread
     let thread_app_state = Arc::clone(&app_state);
     std::thread::spawn(move || {
+        use snake_game::{scenario, GameError};
+
+        let mut puzzles = scenario::PuzzleSequence::new(match game_mode.as_str() {
+            "puzzles" => scenario::puzzles(),
+            "endless" => Vec::new(),
+            _ => {
+                eprintln!("Unknown game mode: {game_mode}");
+                Vec::new()
+            }
+        });
+        let mut game_number = 0;
+
         loop {
-            use snake_game::GameError;
+            let puzzle = puzzles.current().map(|(number, scenario)| {
+                let info = PuzzleInfo {
+                    name: scenario.name.to_owned(),
+                    number,
+                    total: puzzles.len(),
+                };
+                (scenario, info)
+            });
+            match &puzzle {
+                Some((_, info)) => println!(
+                    "New Game - puzzle {}/{}: {}",
+                    info.number, info.total, info.name
+                ),
+                None => println!("New Game"),
+            }
 
-            println!("New Game");
-            if let Err(err) = game_loop(
+            game_number += 1;
+            match game_loop(
                 thread_app_state.as_ref(),
+                game_number,
+                puzzle,
                 &game_exit_recv,
                 preview_send.clone(),
             ) {
-                match err {
-                    GameError::RenderingError | GameError::InvalidInternalState => {
+                Ok(LoopExit::Won) => {
+                    println!("Puzzle solved!");
+                    puzzles.solved();
+                }
+                Ok(LoopExit::BoardFull) => println!("Level is full!"),
+                Ok(LoopExit::Shutdown) => break,
+                Err(err) => match err {
+                    GameError::RenderingError(_)
+                    | GameError::InvalidInternalState(_)
+                    | GameError::InvalidSetup(_) => {
                         eprintln!("{err}");
                         // shutdown server
                         shutdown_sig.send(()).ok();
                         break;
                     }
                     _ => println!("{err}"),
-                }
-            } else {
-                break;
+                },
             }
         }
         println!("Game thread shutdown.");
     });
 
     // terminal renderer thread
-    std::thread::spawn(|| {
-        if let Err(err) = render_game_in_terminal(preview_recv) {
+    let preview_app_state = Arc::clone(&app_state);
+    std::thread::spawn(move || {
+        if let Err(err) = render_game_in_terminal(preview_recv, preview_app_state.as_ref()) {
             eprintln!("Preview rendering: {err}");
         }
         println!("Game preview thread shutdown.");
@@ -72,181 +461,926 @@ async fn main() {
 
     let app = Router::new()
         .route("/snake", get(handle_snake_display))
+        .route(
+            "/snake/votes",
+            get(handle_snake_votes).delete(admin::handle_reset_votes),
+        )
+        .route("/snake/state", get(handle_snake_state))
+        .route("/snake/stream", get(handle_snake_stream))
+        .route("/snake/scores", get(handle_snake_scores))
+        .route("/snake/recent.gif", get(handle_recent_gif))
+        .route("/snake/replay.gif", get(handle_replay_gif))
         .route("/snake/:direction", post(handle_snake_direction))
+        .route("/snake/turn/:turn", post(handle_snake_turn))
+        .route("/bots", post(bots::handle_register))
+        .route("/bots", delete(bots::handle_unregister))
+        .route("/bots/state", get(bots::handle_state))
+        .route("/bots/move/:direction", post(bots::handle_move))
+        .route("/auth/login", post(auth::handle_login))
+        .route("/auth/logout", post(auth::handle_logout))
+        .route("/auth/whoami", get(auth::handle_whoami))
+        .route("/admin/pause", post(admin::handle_pause))
+        .route("/admin/resume", post(admin::handle_resume))
+        .route("/admin/bots/:name", delete(admin::handle_kick))
+        .route("/admin/config", get(admin::handle_config))
+        .route("/admin/snapshot", get(admin::handle_snapshot))
+        .route(
+            "/admin/webhooks",
+            get(admin::handle_webhooks).post(admin::handle_add_webhook),
+        )
+        .route("/admin/webhooks/:id", delete(admin::handle_remove_webhook))
+        .route("/metrics", get(handle_metrics))
+        .route("/healthz", get(health::handle_healthz))
+        .route("/readyz", get(health::handle_readyz))
+        .layer(middleware::from_fn(auth::authorize))
+        .layer(middleware::from_fn(track_http_request))
         .layer(Extension(app_state));
 
-    println!("Game server is running at: {LISTEN_ADDR}");
-    axum::Server::bind(&LISTEN_ADDR.parse().unwrap())
-        .serve(app.into_make_service())
-        .with_graceful_shutdown(async {
-            use tokio::signal;
+    let tls_config = tls_config.map(Arc::new);
+    if let Some(tls_config) = &tls_config {
+        tls::reload_on_sighup(Arc::clone(tls_config));
+    }
+    for listener in &listeners {
+        let tls = if tls_config.is_some() && listener.is_tcp() {
+            " (TLS)"
+        } else {
+            ""
+        };
+        println!("Game server is running at: {listener}{tls}");
+    }
 
-            let ctrl_c = async {
-                signal::ctrl_c()
-                    .await
-                    .expect("failed to install Ctrl+C handler");
-            };
+    let shutdown = shutdown_signal(shutdown_recv, game_exit_sig);
+    let server = axum::Server::builder(listen::Incoming::new(listeners, tls_config))
+        .serve(app.into_make_service_with_connect_info::<listen::PeerAddr>())
+        .with_graceful_shutdown(shutdown);
+    if let Err(err) = server.await {
+        eprintln!("Game server error: {err}");
+    }
+    listen::remove_unix_sockets(&listen_addrs);
+}
 
-            #[cfg(unix)]
-            let terminate = async {
-                signal::unix::signal(signal::unix::SignalKind::terminate())
-                    .expect("failed to install signal handler")
-                    .recv()
-                    .await;
-            };
+/// Resolves on Ctrl+C, SIGTERM or game thread failure, then stops game thread.
+async fn shutdown_signal(shutdown_recv: oneshot::Receiver<()>, game_exit_sig: mpsc::Sender<()>) {
+    use tokio::signal;
 
-            #[cfg(not(unix))]
-            let terminate = std::future::pending::<()>();
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
 
-            tokio::select! {
-                _ = ctrl_c => {},
-                _ = terminate => {},
-                _ = shutdown_recv => {}
-            }
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
 
-            println!("Game server shutdown...");
-            game_exit_sig.send(()).ok(); // shutdown game thread
-        })
-        .await
-        .unwrap();
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+        _ = shutdown_recv => {}
+    }
+
+    println!("Game server shutdown...");
+    game_exit_sig.send(()).ok(); // shutdown game thread
+}
+
+async fn track_http_request<B>(request: Request<B>, next: Next<B>) -> impl IntoResponse {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let app = request.extensions().get::<Arc<AppState>>().cloned();
+
+    let response = next.run(request).await;
+
+    if let Some(app) = app {
+        let status = response.status().as_u16();
+        app.metrics
+            .http_request(&method, &route, status, start.elapsed());
+    }
+    response
+}
+
+async fn handle_metrics(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    match app.metrics.render() {
+        Ok(output) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            output,
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
 }
 
 static LEVEL_TEMPLATE: &str = include_str!("../level.html");
 
-async fn handle_snake_display(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
-    let level_display = app.level_display.lock().await;
-    let output_html = LEVEL_TEMPLATE.replace("{{ level }}", &level_display);
+async fn handle_snake_display(
+    Extension(app): Extension<Arc<AppState>>,
+    Extension(client): Extension<presence::Client>,
+) -> impl IntoResponse {
+    let audience = {
+        let mut presence = app.presence.lock().await;
+        presence.viewed(&client, Instant::now());
+        presence.audience(Instant::now())
+    };
+    let votes = votes_view(&app).await;
+    let puzzle = puzzle_view(&app);
+    let level_display = app
+        .recent_frames
+        .lock()
+        .await
+        .last()
+        .and_then(|frame| frame.to_text().ok())
+        .unwrap_or_default();
+    let output_html = LEVEL_TEMPLATE
+        .replace("{{ puzzle }}", &puzzle)
+        .replace("{{ audience }}", &audience.to_string())
+        .replace("{{ level }}", &level_display)
+        .replace("{{ votes }}", &votes.to_string());
     ([(header::CONTENT_TYPE, "text/html")], output_html)
 }
 
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "lowercase")]
+/// Played puzzle with its goals, empty outside of puzzles mode.
+fn puzzle_view(app: &AppState) -> String {
+    let state = app.state_recv.borrow();
+    let Some(TickState {
+        puzzle: Some(puzzle),
+        state,
+        ..
+    }) = state.as_ref()
+    else {
+        return String::new();
+    };
+
+    let goals = state
+        .win_conditions
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "Puzzle {}/{}: {} - {goals}",
+        puzzle.number, puzzle.total, puzzle.name
+    )
+}
+
+#[derive(serde::Serialize)]
+struct VotesView {
+    pending: HashMap<MovementDirection, usize>,
+    last_tick: Option<votes::VoteResult>,
+    /// directions of level topology, in display order
+    #[serde(skip)]
+    directions: &'static [MovementDirection],
+}
+
+impl std::fmt::Display for VotesView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Pending votes:")?;
+        for direction in self.directions {
+            let count = self.pending.get(direction).unwrap_or(&0);
+            write!(f, " {direction:?}: {count}")?;
+        }
+
+        if let Some(result) = &self.last_tick {
+            write!(f, "\nLast tick ({}): ", result.tick)?;
+            match result.winner {
+                Some(winner) => write!(f, "{winner:?}")?,
+                None => write!(f, "no valid votes")?,
+            }
+            if result.tie_break {
+                write!(f, " (tie-break)")?;
+            }
+            write!(
+                f,
+                ", sampled: {}, discarded: {}, relative: {}",
+                result.sampled.len(),
+                result.discarded,
+                result.relative
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn votes_view(app: &AppState) -> VotesView {
+    let directions = app.topology.directions();
+    let current_direction = app.game_status.lock().await.direction;
+    let pending = votes::tally(
+        &app.selected_moves.lock().await,
+        current_direction,
+        app.topology.as_ref(),
+    );
+    let last_tick = app.last_votes.lock().await.clone();
+    VotesView {
+        pending,
+        last_tick,
+        directions,
+    }
+}
+
+async fn handle_snake_votes(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Json(votes_view(&app).await)
+}
+
+async fn handle_snake_state(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Json(app.state_recv.borrow().clone())
+}
+
+/// Client with open game stream, counted as viewer until dropped.
+struct StreamViewer {
+    app: Arc<AppState>,
+    client: presence::Client,
+}
+
+impl Drop for StreamViewer {
+    fn drop(&mut self) {
+        let presence = Arc::clone(&self.app.presence);
+        let client = self.client.clone();
+        tokio::spawn(async move { presence.lock().await.stream_closed(&client) });
+    }
+}
+
+/// Server sent events stream of game frames: `state` event with full game state
+/// followed by `delta` events with tiles changed on each tick.
+async fn handle_snake_stream(
+    Extension(app): Extension<Arc<AppState>>,
+    Extension(client): Extension<presence::Client>,
+) -> impl IntoResponse {
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use broadcast::error::RecvError;
+
+    app.presence.lock().await.stream_opened(&client);
+    let viewer = StreamViewer {
+        app: Arc::clone(&app),
+        client,
+    };
+
+    // subscribe before reading current state, so no delta after it is missed
+    let frames = app.stream_send.subscribe();
+    let pending = current_frame(&app);
+
+    // game and tick of the last frame sent to client
+    let sent: Option<(u64, u64)> = None;
+    let stream = futures_util::stream::unfold(
+        (viewer, frames, sent, pending),
+        |(viewer, mut frames, mut sent, mut pending)| async move {
+            loop {
+                let frame = match pending.take() {
+                    Some(frame) => frame,
+                    None => match frames.recv().await {
+                        Ok(frame) => frame,
+                        // client is too slow, start over from current state
+                        Err(RecvError::Lagged(_)) => match current_frame(&viewer.app) {
+                            Some(frame) => frame,
+                            None => continue,
+                        },
+                        Err(RecvError::Closed) => return None,
+                    },
+                };
+
+                let event = match frame {
+                    StreamFrame::State(state) => {
+                        sent = Some((state.game, state.tick));
+                        Event::default().event("state").json_data(state.as_ref())
+                    }
+                    StreamFrame::Delta(delta) => match sent {
+                        // delta already included in state sent to client
+                        Some(sent) if (delta.game, delta.tick) <= sent => continue,
+                        Some((game, tick)) if (delta.game, delta.tick) == (game, tick + 1) => {
+                            sent = Some((delta.game, delta.tick));
+                            Event::default().event("delta").json_data(delta.as_ref())
+                        }
+                        // ticks were missed, start over from current state
+                        _ => {
+                            pending = current_frame(&viewer.app);
+                            continue;
+                        }
+                    },
+                };
+                return Some((event, (viewer, frames, sent, pending)));
+            }
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Full state of current tick for stream clients starting over.
+fn current_frame(app: &AppState) -> Option<StreamFrame> {
+    let state = app.state_recv.borrow().clone()?;
+    Some(StreamFrame::State(Arc::new(state)))
+}
+
+async fn handle_snake_scores(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Json(app.scores.lock().await.view())
+}
+
+async fn handle_recent_gif(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let frames = app.recent_frames.lock().await.clone();
+    if frames.is_empty() {
+        return (StatusCode::NOT_FOUND, "No frames recorded").into_response();
+    }
+    gif_response(encode_gif(frames).await)
+}
+
+async fn handle_replay_gif(Extension(app): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let replay = Arc::clone(&*app.last_replay.lock().await);
+    if replay.frames.is_empty() {
+        return (StatusCode::NOT_FOUND, "No frames recorded").into_response();
+    }
+    let gif = replay
+        .gif
+        .get_or_try_init(|| encode_gif(replay.frames.clone()))
+        .await;
+    gif_response(gif.cloned())
+}
+
+/// Encode frames off async runtime, they may be thousands.
+async fn encode_gif(frames: FrameBuffer) -> Result<Bytes, ()> {
+    // hundredths of a second
+    let frame_delay = (FRAME_TIME.as_millis() / 10) as u16;
+    let encoded =
+        tokio::task::spawn_blocking(move || recording::encode_gif(frames.frames(), frame_delay))
+            .await;
+
+    match encoded {
+        Ok(Ok(gif)) => Ok(Bytes::from(gif)),
+        _ => Err(()),
+    }
+}
+
+fn gif_response(gif: Result<Bytes, ()>) -> axum::response::Response {
+    match gif {
+        Ok(gif) => ([(header::CONTENT_TYPE, "image/gif")], gif).into_response(),
+        Err(()) => (StatusCode::INTERNAL_SERVER_ERROR, "Gif encoding failed").into_response(),
+    }
+}
+
 enum Direction {
     Left,
     Right,
-    #[serde(alias = "down")]
     Bottom,
-    #[serde(alias = "up")]
     Top,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Direction from request path, it has to be supported by level topology.
+fn parse_direction(
+    direction: &str,
+    topology: &dyn Topology,
+) -> Result<MovementDirection, &'static str> {
+    let mov = match direction.parse() {
+        Ok(Direction::Left) => MovementDirection::Left,
+        Ok(Direction::Right) => MovementDirection::Right,
+        Ok(Direction::Bottom) => MovementDirection::Down,
+        Ok(Direction::Top) => MovementDirection::Up,
+        Ok(Direction::TopLeft) => MovementDirection::UpLeft,
+        Ok(Direction::TopRight) => MovementDirection::UpRight,
+        Ok(Direction::BottomLeft) => MovementDirection::DownLeft,
+        Ok(Direction::BottomRight) => MovementDirection::DownRight,
+        Err(_) => return Err("unknown direction"),
+    };
+
+    if topology.supports(mov) {
+        Ok(mov)
+    } else {
+        Err("direction not supported by level topology")
+    }
+}
+
+impl std::str::FromStr for Direction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "bottom" | "down" => Ok(Self::Bottom),
+            "top" | "up" => Ok(Self::Top),
+            "top-left" | "up-left" | "up_left" => Ok(Self::TopLeft),
+            "top-right" | "up-right" | "up_right" => Ok(Self::TopRight),
+            "bottom-left" | "down-left" | "down_left" => Ok(Self::BottomLeft),
+            "bottom-right" | "down-right" | "down_right" => Ok(Self::BottomRight),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct VoteAccepted {
+    /// voted direction, for turns resolved against current direction
+    direction: MovementDirection,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    turn: Option<Turn>,
+    /// position of vote in queue of votes for next tick (counting from 1)
+    queued_position: usize,
+    /// tick on which vote will be counted
+    target_tick: u64,
+    current_direction: MovementDirection,
+}
+
+#[derive(serde::Serialize)]
+struct VoteRejected {
+    error: &'static str,
+    direction: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_direction: Option<MovementDirection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid_directions: Option<Vec<&'static str>>,
 }
 
 async fn handle_snake_direction(
     Extension(app): Extension<Arc<AppState>>,
-    Path(direction_command): Path<Direction>,
-) -> impl IntoResponse {
-    let mov = match direction_command {
-        Direction::Left => MovementDirection::Left,
-        Direction::Right => MovementDirection::Right,
-        Direction::Bottom => MovementDirection::Down,
-        Direction::Top => MovementDirection::Up,
+    Extension(client): Extension<presence::Client>,
+    Path(direction_command): Path<String>,
+) -> Response {
+    // `straight` shares path with absolute directions
+    if direction_command == "straight" {
+        let vote = Vote::Relative(Turn::Straight);
+        return accept_vote(&app, &client, vote, direction_command).await;
+    }
+
+    let mov = match parse_direction(&direction_command, app.topology.as_ref()) {
+        Ok(mov) => mov,
+        Err(error) => {
+            let rejected = VoteRejected {
+                error,
+                direction: direction_command,
+                current_direction: None,
+                valid_directions: Some(
+                    app.topology.directions().iter().map(|d| d.name()).collect(),
+                ),
+            };
+            return (StatusCode::BAD_REQUEST, Json(rejected)).into_response();
+        }
+    };
+    accept_vote(&app, &client, Vote::Absolute(mov), direction_command).await
+}
+
+/// Vote for turn relative to snake direction on the tick vote is counted.
+async fn handle_snake_turn(
+    Extension(app): Extension<Arc<AppState>>,
+    Extension(client): Extension<presence::Client>,
+    Path(turn_command): Path<String>,
+) -> Response {
+    let turn = match turn_command.as_str() {
+        "left" => Turn::Left,
+        "right" => Turn::Right,
+        _ => {
+            let rejected = VoteRejected {
+                error: "unknown turn",
+                direction: turn_command,
+                current_direction: None,
+                valid_directions: Some(vec!["left", "right"]),
+            };
+            return (StatusCode::BAD_REQUEST, Json(rejected)).into_response();
+        }
     };
+    let command = format!("turn/{turn_command}");
+    accept_vote(&app, &client, Vote::Relative(turn), command).await
+}
+
+/// Vote response: `201` with accepted vote, or status with rejection reason.
+async fn accept_vote(
+    app: &AppState,
+    client: &presence::Client,
+    vote: Vote,
+    command: String,
+) -> Response {
+    match submit_vote(app, client, vote, command).await {
+        Ok(accepted) => (StatusCode::CREATED, Json(accepted)).into_response(),
+        Err((status, rejected)) => (status, Json(rejected)).into_response(),
+    }
+}
+
+/// Queue vote for next tick, shared by HTTP and chat voting.
+async fn submit_vote(
+    app: &AppState,
+    client: &presence::Client,
+    vote: Vote,
+    command: String,
+) -> Result<VoteAccepted, (StatusCode, VoteRejected)> {
+    let (tick, current_direction) = {
+        let status = app.game_status.lock().await;
+        (status.tick, status.direction)
+    };
+
+    if !app.steering.allows(&vote) {
+        let rejected = VoteRejected {
+            error: "voting style not allowed",
+            direction: command,
+            current_direction: Some(current_direction),
+            valid_directions: None,
+        };
+        return Err((StatusCode::FORBIDDEN, rejected));
+    }
+
+    let mov = vote.resolve(current_direction, app.topology.as_ref());
+    app.metrics.vote_received(mov);
+
+    // votes would pile up until game is resumed
+    if *app.paused.lock().await {
+        let rejected = VoteRejected {
+            error: "game is paused",
+            direction: command,
+            current_direction: Some(current_direction),
+            valid_directions: None,
+        };
+        return Err((StatusCode::LOCKED, rejected));
+    }
+
+    if app.bots.lock().await.driver_name().is_some() {
+        let rejected = VoteRejected {
+            error: "game is driven by bot",
+            direction: command,
+            current_direction: Some(current_direction),
+            valid_directions: None,
+        };
+        return Err((StatusCode::LOCKED, rejected));
+    }
+
+    // vote would be discarded on next tick
+    if mov.is_opposite_to(&current_direction) {
+        app.metrics.votes_discarded(mov, 1);
+        let rejected = VoteRejected {
+            error: "direction opposite to snake movement",
+            direction: command,
+            current_direction: Some(current_direction),
+            valid_directions: None,
+        };
+        return Err((StatusCode::CONFLICT, rejected));
+    }
 
     let mut moves = app.selected_moves.lock().await;
-    moves.push(mov);
+    moves.push(vote);
+    app.presence.lock().await.voted(client, Instant::now());
+    app.metrics.vote_accepted(mov);
 
-    StatusCode::CREATED
+    let accepted = VoteAccepted {
+        direction: mov,
+        turn: match vote {
+            Vote::Relative(turn) => Some(turn),
+            Vote::Absolute(_) => None,
+        },
+        queued_position: moves.len(),
+        target_tick: tick + 1,
+        current_direction,
+    };
+    Ok(accepted)
 }
 
 fn game_loop<T>(
     app_state: &AppState,
+    game_number: u64,
+    puzzle: Option<(&snake_game::scenario::Scenario, PuzzleInfo)>,
     end_sig: &mpsc::Receiver<T>,
-    preview_send: mpsc::Sender<String>,
-) -> Result<(), snake_game::GameError> {
+    preview_send: mpsc::Sender<PreviewFrame>,
+) -> Result<LoopExit, snake_game::GameError> {
     use snake_game::{
-        fruit::FruitRandomLimited, renderer::GameDisplayToString, snake::SnakeUnbounded, Game,
-        GameLevel,
+        controller,
+        renderer::{GameDisplayToString, GameDisplayToTiles, GameStateTracker},
+        GameBuilder, GameOutcome,
     };
-    use std::time::Instant;
 
-    let level = GameLevel::new(LEVEL_WIDTH, LEVEL_HEIGHT);
-    let snake = SnakeUnbounded::new(MovementDirection::Right);
-    let fruit = FruitRandomLimited::new(MAX_FRUITS, NEW_FRUIT_CHANCE);
-    let mut game = Game::new(level, snake, fruit);
-    game.put_snake(SNAKE_TAIL_SIZE)?;
+    let (builder, puzzle) = match puzzle {
+        Some((scenario, info)) => (scenario.builder()?, Some(info)),
+        None => (
+            GameBuilder::new(LEVEL_WIDTH, LEVEL_HEIGHT)
+                .topology(app_state.topology.clone())
+                .boundary(app_state.boundary)
+                .direction(MovementDirection::Right)
+                .tail_size(SNAKE_TAIL_SIZE)
+                .random_fruits(MAX_FRUITS, NEW_FRUIT_CHANCE),
+            None,
+        ),
+    };
+    let mut game = builder.build()?;
+    game.set_input_buffer(INPUT_BUFFER_DEPTH);
+    app_state.metrics.game_started();
+    app_state
+        .events
+        .send(GameEvent::GameStarted {
+            game: game_number,
+            puzzle: puzzle.as_ref().map(|p| p.name.clone()),
+        })
+        .ok();
 
     let renderer = GameDisplayToString;
-    // initial render
-    let output = game.render(&renderer)?;
-    preview_send.send(output.to_owned()).ok();
-    {
-        let mut display = app_state.level_display.blocking_lock();
-        *display = output;
-    }
+    let mut replay = FrameBuffer::new(MAX_REPLAY_FRAMES);
 
     let mut move_timer = Instant::now();
+    let mut tick = 0u64;
+    let ai_controller_name =
+        std::env::var("SNAPI_AI_CONTROLLER").unwrap_or_else(|_| AI_CONTROLLER.to_owned());
+    let ai_controller = match controller::by_name(&ai_controller_name, None) {
+        Some(ai_controller) => ai_controller,
+        None => {
+            eprintln!("Unknown AI controller: {ai_controller_name}");
+            controller::by_name(AI_CONTROLLER, None).ok_or(
+                snake_game::GameError::InvalidInternalState("unknown AI controller"),
+            )?
+        }
+    };
+    let mut driver = votes::CrowdDriver::new(ai_controller, AI_FALLBACK_TICKS);
+
+    // initial render
+    app_state.health.tick();
+    game.take_changes();
+    let output = game.render(&renderer)?;
+    send_preview(app_state, &preview_send, PreviewFrame::Full(output));
+    let frame = game.render(&GameDisplayToTiles)?;
+    app_state.recent_frames.blocking_lock().push(frame.clone());
+    replay.push(frame);
+    let mut objects = GameStateTracker::new(&game);
+    let state = objects.state(&game)?;
+    let mut fruits_count = state.fruits.len();
+    publish_state(
+        app_state,
+        game_number,
+        tick,
+        &puzzle,
+        state,
+        game.last_queued_direction(),
+        game.queued_turns(),
+    );
 
     loop {
         if end_sig.try_recv().is_ok() {
-            return Ok(());
+            return Ok(LoopExit::Shutdown);
+        }
+        if move_timer.elapsed() > FRAME_TIME && *app_state.paused.blocking_lock() {
+            // paused game is alive, it just does not move
+            move_timer = Instant::now();
+            app_state.health.tick();
+            continue;
         }
         if move_timer.elapsed() > FRAME_TIME {
             move_timer = Instant::now();
 
-            // get available moves (with draining selected moves)
-            let available_moves = {
-                let mut moves = app_state.selected_moves.blocking_lock();
-                let current_direction = game.snake().direction();
-                moves
-                    .drain(..)
-                    .filter(|d| !d.is_opposite_to(&current_direction))
-                    .collect::<Vec<_>>()
-                // drop lock
-            };
-            if !available_moves.is_empty() {
-                use rand::seq::SliceRandom;
-                let mut r = rand::thread_rng();
-
-                // pick random five
-                let selected = available_moves.choose_multiple(&mut r, 5).cloned();
-                let (most_occurrences, mut selected_count) =
-                    helper::get_most_move_occurrences_in(selected);
-
-                // retain moves with most occurrences
-                let selected = selected_count
-                    .drain()
-                    .filter_map(|(mov, count)| {
-                        if count == most_occurrences {
-                            Some(mov)
-                        } else {
-                            None
-                        }
+            tick += 1;
+            app_state.health.tick();
+            let length_before = game.snake().len();
+
+            let driver_moves = app_state.bots.blocking_lock().take_driver_moves();
+            match driver_moves {
+                // snake driven by bot, crowd votes are ignored
+                Some(bot_moves) => {
+                    app_state.selected_moves.blocking_lock().clear();
+                    app_state.presence.blocking_lock().clear_tick_voters();
+                    // turns are validated when queued, see `Bots::queue_move`
+                    driver.steer_by_bot(&mut game, bot_moves);
+                }
+                None => {
+                    // select movement from votes (with draining selected moves)
+                    let (votes, turnout) = {
+                        let mut moves = app_state.selected_moves.blocking_lock();
+                        let turnout = app_state.presence.blocking_lock().end_tick(Instant::now());
+                        (moves.drain(..).collect(), turnout)
+                        // drop lock
+                    };
+                    let current_direction = game.snake().direction();
+                    let ai_steering = driver.ai_steering();
+                    let vote_result =
+                        driver.steer(&mut game, &app_state.strategy, tick, votes, turnout)?;
+                    app_state
+                        .metrics
+                        .votes_discarded(current_direction.opposite(), vote_result.discarded);
+                    if !ai_steering && driver.ai_steering() {
+                        println!("No votes, AI controller takes over.");
+                    }
+                    *app_state.last_votes.blocking_lock() = Some(vote_result);
+                }
+            }
+
+            let outcome = game.try_move();
+            if !matches!(outcome, Ok(GameOutcome::Running)) {
+                // keep finished game for replay
+                *app_state.last_replay.blocking_lock() = Arc::new(Replay::new(replay));
+                let score = scores::GameScore {
+                    ticks: tick,
+                    length: game.snake().len(),
+                    death: match &outcome {
+                        Err(snake_game::GameError::GameOver(cause)) => Some(*cause),
+                        _ => None,
+                    },
+                    bot: app_state
+                        .bots
+                        .blocking_lock()
+                        .driver_name()
+                        .map(String::from),
+                    won: matches!(outcome, Ok(GameOutcome::Won | GameOutcome::BoardFull)),
+                    puzzle: puzzle.as_ref().map(|p| p.name.to_owned()),
+                };
+                println!("Snake length: {}", score.length);
+                let mut scores = app_state.scores.blocking_lock();
+                let high_score = scores
+                    .best_length()
+                    .filter(|best| score.length > *best)
+                    .map(|previous| GameEvent::HighScore {
+                        game: game_number,
+                        length: score.length,
+                        previous,
+                    });
+                app_state
+                    .events
+                    .send(GameEvent::GameOver {
+                        game: game_number,
+                        score: score.clone(),
                     })
-                    .collect::<Vec<_>>();
+                    .ok();
+                if let Some(high_score) = high_score {
+                    app_state.events.send(high_score).ok();
+                }
+                scores.record(score);
+                drop(scores);
 
-                // finally pick movement, unwrap is ok - selected vec cannot be empty
-                let movement = *selected.choose(&mut r).unwrap();
-                game.set_snake_direction(movement)?;
+                return match outcome {
+                    Err(err) => {
+                        app_state.metrics.game_ended(game_end_cause(&err));
+                        Err(err)
+                    }
+                    Ok(GameOutcome::BoardFull) => {
+                        app_state.metrics.game_ended("board_full");
+                        Ok(LoopExit::BoardFull)
+                    }
+                    Ok(_) => {
+                        app_state.metrics.game_ended("won");
+                        Ok(LoopExit::Won)
+                    }
+                };
             }
 
-            game.try_move()?;
+            let changes = game.take_changes();
+            send_preview(
+                app_state,
+                &preview_send,
+                PreviewFrame::Delta(changes.clone()),
+            );
+            objects.apply(&changes);
+            let state = objects.state(&game)?;
+
+            let eaten = state.length.saturating_sub(length_before);
+            for length in LENGTH_MILESTONES {
+                if length_before < length && length <= state.length {
+                    let milestone = GameEvent::Milestone {
+                        game: game_number,
+                        tick,
+                        length,
+                    };
+                    app_state.events.send(milestone).ok();
+                }
+            }
+            let spawned = (state.fruits.len() + eaten).saturating_sub(fruits_count);
+            fruits_count = state.fruits.len();
+            app_state
+                .metrics
+                .tick(move_timer.elapsed(), state.length, spawned, eaten);
 
-            let output = game.render(&renderer)?;
-            preview_send.send(output.to_owned()).ok();
-            let mut display = app_state.level_display.blocking_lock();
-            *display = output;
+            publish_state(
+                app_state,
+                game_number,
+                tick,
+                &puzzle,
+                state,
+                game.last_queued_direction(),
+                game.queued_turns(),
+            );
+            // sent after state, so stream clients reading state on delta never see older tick
+            app_state
+                .recent_frames
+                .blocking_lock()
+                .push_changes(changes.clone());
+            replay.push_changes(changes.clone());
+            app_state
+                .stream_send
+                .send(StreamFrame::Delta(Arc::new(TickDelta {
+                    game: game_number,
+                    tick,
+                    changes,
+                })))
+                .ok();
         }
         // slowdown
         std::thread::sleep(std::time::Duration::from_micros(10));
     }
 }
 
-fn render_game_in_terminal(output_recv: mpsc::Receiver<String>) -> std::io::Result<()> {
-    use crossterm::{cursor, execute, style, terminal};
+fn game_end_cause(err: &snake_game::GameError) -> &'static str {
+    use snake_game::GameError;
+
+    match err {
+        GameError::GameOver(cause) => cause.name(),
+        GameError::InvalidInternalState(_) => "invalid_state",
+        GameError::InvalidSetup(_) => "invalid_setup",
+        GameError::UnsupportedDirection(_) => "unsupported_direction",
+        GameError::RenderingError(_) => "rendering_error",
+    }
+}
+
+fn publish_state(
+    app_state: &AppState,
+    game: u64,
+    tick: u64,
+    puzzle: &Option<PuzzleInfo>,
+    state: snake_game::renderer::GameState,
+    queued_direction: MovementDirection,
+    queued_turns: usize,
+) {
+    *app_state.game_status.blocking_lock() = GameStatus {
+        tick,
+        direction: state.direction,
+        queued_direction,
+        queued_turns,
+        published: Instant::now(),
+    };
+    let audience = app_state.presence.blocking_lock().audience(Instant::now());
+    app_state
+        .metrics
+        .audience(audience.viewers, audience.voters);
+    let tick_state = TickState {
+        game,
+        tick,
+        deadline_ms: BOT_MOVE_DEADLINE.as_millis() as u64,
+        puzzle: puzzle.clone(),
+        audience,
+        state,
+    };
+    // stream starts over with full state of new game
+    if tick == 0 {
+        app_state
+            .stream_send
+            .send(StreamFrame::State(Arc::new(tick_state.clone())))
+            .ok();
+    }
+    app_state.state_send.send_replace(Some(tick_state));
+}
+
+fn send_preview(
+    app_state: &AppState,
+    preview_send: &mpsc::Sender<PreviewFrame>,
+    frame: PreviewFrame,
+) {
+    // counted before send, preview thread may render frame before send returns
+    app_state.health.preview_sent();
+    // err on send means preview is not running
+    if preview_send.send(frame).is_err() {
+        app_state.health.preview_dropped();
+    }
+}
+
+fn render_game_in_terminal(
+    output_recv: mpsc::Receiver<PreviewFrame>,
+    app_state: &AppState,
+) -> std::io::Result<()> {
+    use crossterm::{cursor, execute, queue, style, terminal};
+    use snake_game::renderer::tile_char;
     use std::io::{stdout, Write};
     let mut stdout = stdout();
+    let topology = app_state.topology.as_ref();
 
     // block thread until new output is available
     // err on recv means channel is closed - game exit
-    while let Ok(output) = output_recv.recv() {
-        execute!(
-            stdout,
-            cursor::SavePosition,
-            terminal::Clear(terminal::ClearType::FromCursorDown),
-            style::Print(&output),
-            cursor::RestorePosition,
-        )?;
+    while let Ok(frame) = output_recv.recv() {
+        match frame {
+            PreviewFrame::Full(output) => queue!(
+                stdout,
+                cursor::SavePosition,
+                terminal::Clear(terminal::ClearType::FromCursorDown),
+                style::Print(&output),
+                cursor::RestorePosition,
+            )?,
+            // repaint changed tiles only, level is framed with walls
+            PreviewFrame::Delta(changes) => {
+                for change in changes {
+                    let (column, row) = topology.text_cell(change.x, change.y);
+                    let cell = std::iter::repeat_n(tile_char(change.tile), topology.cell_width());
+                    queue!(
+                        stdout,
+                        cursor::SavePosition,
+                        cursor::MoveDown(row as u16 + 1),
+                        cursor::MoveRight(column as u16 + 1),
+                        style::Print(cell.collect::<String>()),
+                        cursor::RestorePosition,
+                    )?;
+                }
+            }
+        }
 
         stdout.flush()?;
+        app_state.health.preview_rendered();
     }
 
     // clear display
@@ -255,3 +1389,103 @@ fn render_game_in_terminal(output_recv: mpsc::Receiver<String>) -> std::io::Resu
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{presence::Client, AppState};
+    use axum::{
+        extract::Path,
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        Extension,
+    };
+    use std::sync::Arc;
+
+    async fn vote(app: &Arc<AppState>, direction: &str) -> (StatusCode, serde_json::Value) {
+        let response: Response = super::handle_snake_direction(
+            Extension(Arc::clone(app)),
+            Extension(Client::from_addr([192, 0, 2, 1].into())),
+            Path(direction.to_owned()),
+        )
+        .await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn replay_gif_is_encoded_once() {
+        use super::{recording::Replay, FrameBuffer};
+        use snapi::snake_game::{renderer::TileFrame, topology, TileType};
+
+        let app = Arc::new(AppState::default());
+        let response = super::handle_replay_gif(Extension(Arc::clone(&app)))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let mut frames = FrameBuffer::new(2);
+        frames.push(TileFrame {
+            width: 2,
+            height: 1,
+            tiles: vec![TileType::Snake, TileType::Fruit],
+            topology: topology::by_name("square").unwrap(),
+        });
+        *app.last_replay.lock().await = Arc::new(Replay::new(frames));
+
+        let response = super::handle_replay_gif(Extension(Arc::clone(&app)))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let cached = app.last_replay.lock().await.gif.get().cloned();
+        assert_eq!(cached, Some(body));
+    }
+
+    #[tokio::test]
+    async fn accepted_vote_is_described() {
+        let app = Arc::new(AppState::default());
+
+        let (status, body) = vote(&app, "up").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["direction"], "up");
+        assert_eq!(body["queued_position"], 1);
+        assert_eq!(body["target_tick"], 1);
+        assert_eq!(body["current_direction"], "right");
+        assert_eq!(app.selected_moves.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejected_votes_have_status_and_reason() {
+        let app = Arc::new(AppState::default());
+
+        let (status, body) = vote(&app, "sideways").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "unknown direction");
+        assert_eq!(
+            body["valid_directions"],
+            serde_json::json!(["up", "down", "left", "right"])
+        );
+        let (status, body) = vote(&app, "up-left").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["valid_directions"].is_array());
+
+        // snake moves right
+        let (status, body) = vote(&app, "left").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["current_direction"], "right");
+
+        *app.paused.lock().await = true;
+        let (status, body) = vote(&app, "up").await;
+        assert_eq!(status, StatusCode::LOCKED);
+        assert_eq!(body["error"], "game is paused");
+        *app.paused.lock().await = false;
+
+        app.bots.lock().await.register("driver", true).unwrap();
+        let (status, body) = vote(&app, "up").await;
+        assert_eq!(status, StatusCode::LOCKED);
+        assert_eq!(body["error"], "game is driven by bot");
+
+        assert!(app.selected_moves.lock().await.is_empty());
+    }
+}
@@ -0,0 +1,323 @@
+use snapi::snake_game::MovementDirection;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const TICK_BUCKETS: [f64; 8] = [0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.05, 0.1];
+const HTTP_BUCKETS: [f64; 9] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Counter for every movement direction.
+#[derive(Default)]
+struct DirectionCounter([AtomicU64; MovementDirection::ALL.len()]);
+
+impl DirectionCounter {
+    fn index(direction: MovementDirection) -> usize {
+        MovementDirection::ALL
+            .iter()
+            .position(|d| *d == direction)
+            .unwrap_or_default()
+    }
+
+    fn add(&self, direction: MovementDirection, count: u64) {
+        self.0[Self::index(direction)].fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn write(&self, output: &mut String, name: &str, help: &str) -> std::fmt::Result {
+        writeln!(output, "# HELP {name} {help}")?;
+        writeln!(output, "# TYPE {name} counter")?;
+        for direction in MovementDirection::ALL {
+            let value = self.0[Self::index(direction)].load(Ordering::Relaxed);
+            let label = direction.name();
+            writeln!(output, "{name}{{direction=\"{label}\"}} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+struct Histogram {
+    buckets: &'static [f64],
+    /// count of observations for every bucket (not cumulative)
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = self.buckets.iter().position(|b| value <= *b) {
+            self.counts[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn write(&self, output: &mut String, name: &str, labels: &str) -> std::fmt::Result {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bucket, count) in self.buckets.iter().zip(self.counts.iter()) {
+            cumulative += count;
+            writeln!(
+                output,
+                "{name}_bucket{{{labels}{separator}le=\"{bucket}\"}} {cumulative}"
+            )?;
+        }
+        writeln!(
+            output,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        )?;
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        writeln!(output, "{name}_sum{labels} {}", self.sum)?;
+        writeln!(output, "{name}_count{labels} {}", self.count)
+    }
+}
+
+/// Server metrics exposed in Prometheus text format.
+pub struct Metrics {
+    votes_received: DirectionCounter,
+    votes_accepted: DirectionCounter,
+    votes_discarded: DirectionCounter,
+    ticks: AtomicU64,
+    games_started: AtomicU64,
+    games_ended: Mutex<BTreeMap<String, u64>>,
+    fruits_spawned: AtomicU64,
+    fruits_eaten: AtomicU64,
+    snake_length: AtomicU64,
+    viewers: AtomicU64,
+    active_voters: AtomicU64,
+    tick_duration: Mutex<Histogram>,
+    /// requests count by (method, route, status)
+    http_requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// requests latency by route
+    http_duration: Mutex<BTreeMap<String, Histogram>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            votes_received: Default::default(),
+            votes_accepted: Default::default(),
+            votes_discarded: Default::default(),
+            ticks: Default::default(),
+            games_started: Default::default(),
+            games_ended: Default::default(),
+            fruits_spawned: Default::default(),
+            fruits_eaten: Default::default(),
+            snake_length: Default::default(),
+            viewers: Default::default(),
+            active_voters: Default::default(),
+            tick_duration: Mutex::new(Histogram::new(&TICK_BUCKETS)),
+            http_requests: Default::default(),
+            http_duration: Default::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn vote_received(&self, direction: MovementDirection) {
+        self.votes_received.add(direction, 1);
+    }
+
+    pub fn vote_accepted(&self, direction: MovementDirection) {
+        self.votes_accepted.add(direction, 1);
+    }
+
+    pub fn votes_discarded(&self, direction: MovementDirection, count: usize) {
+        self.votes_discarded.add(direction, count as u64);
+    }
+
+    pub fn game_started(&self) {
+        self.games_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn game_ended(&self, cause: &str) {
+        let mut games_ended = self.games_ended.lock().unwrap();
+        *games_ended.entry(cause.to_owned()).or_default() += 1;
+    }
+
+    pub fn tick(&self, duration: Duration, snake_length: usize, spawned: usize, eaten: usize) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.snake_length
+            .store(snake_length as u64, Ordering::Relaxed);
+        self.fruits_spawned
+            .fetch_add(spawned as u64, Ordering::Relaxed);
+        self.fruits_eaten.fetch_add(eaten as u64, Ordering::Relaxed);
+        self.tick_duration
+            .lock()
+            .unwrap()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn audience(&self, viewers: usize, active_voters: usize) {
+        self.viewers.store(viewers as u64, Ordering::Relaxed);
+        self.active_voters
+            .store(active_voters as u64, Ordering::Relaxed);
+    }
+
+    pub fn http_request(&self, method: &str, route: &str, status: u16, duration: Duration) {
+        let key = (method.to_owned(), route.to_owned(), status);
+        *self.http_requests.lock().unwrap().entry(key).or_default() += 1;
+        self.http_duration
+            .lock()
+            .unwrap()
+            .entry(route.to_owned())
+            .or_insert_with(|| Histogram::new(&HTTP_BUCKETS))
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn render(&self) -> Result<String, std::fmt::Error> {
+        let mut output = String::new();
+
+        self.votes_received.write(
+            &mut output,
+            "snapi_votes_received_total",
+            "Votes received by direction.",
+        )?;
+        self.votes_accepted.write(
+            &mut output,
+            "snapi_votes_accepted_total",
+            "Votes queued for game tick by direction.",
+        )?;
+        self.votes_discarded.write(
+            &mut output,
+            "snapi_votes_discarded_total",
+            "Votes discarded as opposite to snake movement by direction.",
+        )?;
+
+        let counters = [
+            ("snapi_ticks_total", "Game ticks.", &self.ticks),
+            (
+                "snapi_games_started_total",
+                "Games started.",
+                &self.games_started,
+            ),
+            (
+                "snapi_fruits_spawned_total",
+                "Fruits spawned.",
+                &self.fruits_spawned,
+            ),
+            (
+                "snapi_fruits_eaten_total",
+                "Fruits eaten.",
+                &self.fruits_eaten,
+            ),
+        ];
+        for (name, help, value) in counters {
+            writeln!(output, "# HELP {name} {help}")?;
+            writeln!(output, "# TYPE {name} counter")?;
+            writeln!(output, "{name} {}", value.load(Ordering::Relaxed))?;
+        }
+
+        writeln!(
+            output,
+            "# HELP snapi_games_ended_total Games ended by cause."
+        )?;
+        writeln!(output, "# TYPE snapi_games_ended_total counter")?;
+        for (cause, count) in self.games_ended.lock().unwrap().iter() {
+            writeln!(
+                output,
+                "snapi_games_ended_total{{cause=\"{cause}\"}} {count}"
+            )?;
+        }
+
+        let gauges = [
+            (
+                "snapi_snake_length",
+                "Current snake length.",
+                &self.snake_length,
+            ),
+            ("snapi_viewers", "Distinct recent viewers.", &self.viewers),
+            (
+                "snapi_active_voters",
+                "Distinct recently active voters.",
+                &self.active_voters,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            writeln!(output, "# HELP {name} {help}")?;
+            writeln!(output, "# TYPE {name} gauge")?;
+            writeln!(output, "{name} {}", value.load(Ordering::Relaxed))?;
+        }
+
+        writeln!(
+            output,
+            "# HELP snapi_tick_duration_seconds Game tick processing time."
+        )?;
+        writeln!(output, "# TYPE snapi_tick_duration_seconds histogram")?;
+        self.tick_duration
+            .lock()
+            .unwrap()
+            .write(&mut output, "snapi_tick_duration_seconds", "")?;
+
+        writeln!(output, "# HELP snapi_http_requests_total HTTP requests.")?;
+        writeln!(output, "# TYPE snapi_http_requests_total counter")?;
+        for ((method, route, status), count) in self.http_requests.lock().unwrap().iter() {
+            writeln!(
+                output,
+                "snapi_http_requests_total{{method=\"{method}\",route=\"{route}\",status=\"{status}\"}} {count}"
+            )?;
+        }
+
+        writeln!(
+            output,
+            "# HELP snapi_http_request_duration_seconds HTTP request latency."
+        )?;
+        writeln!(
+            output,
+            "# TYPE snapi_http_request_duration_seconds histogram"
+        )?;
+        for (route, histogram) in self.http_duration.lock().unwrap().iter() {
+            histogram.write(
+                &mut output,
+                "snapi_http_request_duration_seconds",
+                &format!("route=\"{route}\""),
+            )?;
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metrics;
+    use snapi::snake_game::MovementDirection;
+    use std::time::Duration;
+
+    #[test]
+    fn metrics_text_format() {
+        let metrics = Metrics::default();
+        metrics.vote_received(MovementDirection::Up);
+        metrics.votes_discarded(MovementDirection::Left, 2);
+        metrics.game_ended("game_over");
+        metrics.tick(Duration::from_micros(700), 3, 1, 0);
+        metrics.http_request("GET", "/snake", 200, Duration::from_millis(2));
+
+        let output = metrics.render().unwrap();
+        assert!(output.contains("snapi_votes_received_total{direction=\"up\"} 1\n"));
+        assert!(output.contains("snapi_votes_discarded_total{direction=\"left\"} 2\n"));
+        assert!(output.contains("snapi_games_ended_total{cause=\"game_over\"} 1\n"));
+        assert!(output.contains("snapi_snake_length 3\n"));
+        assert!(output.contains("snapi_tick_duration_seconds_bucket{le=\"0.0005\"} 0\n"));
+        assert!(output.contains("snapi_tick_duration_seconds_bucket{le=\"0.001\"} 1\n"));
+        assert!(output.contains(
+            "snapi_http_requests_total{method=\"GET\",route=\"/snake\",status=\"200\"} 1\n"
+        ));
+        assert!(output.contains("snapi_http_request_duration_seconds_count{route=\"/snake\"} 1\n"));
+    }
+}
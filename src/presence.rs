@@ -0,0 +1,197 @@
+use crate::listen::PeerAddr;
+use crate::votes::Turnout;
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// viewer is counted for this long after opening game page
+const VIEWER_WINDOW: Duration = Duration::from_secs(30);
+/// voter is active for this long after last vote (25 ticks)
+const VOTER_WINDOW: Duration = Duration::from_secs(5);
+
+/// Client identity for counting viewers and voters: validated credentials when present,
+/// address otherwise. Credentials are hashed, so identity can be logged.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Client(String);
+
+impl Client {
+    /// Client of request with given bearer token or session cookie and peer address,
+    /// credentials must be validated by caller.
+    /// Forwarded address is trusted only from local peers (reverse proxy).
+    pub fn identify(credentials: Option<&str>, headers: &HeaderMap, peer: PeerAddr) -> Self {
+        if let Some(credentials) = credentials {
+            let digest = Sha256::digest(credentials.as_bytes());
+            let id = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+            return Self(id);
+        }
+
+        let local = peer.0.is_none_or(|ip| ip.is_loopback());
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            // last address is added by our proxy, others are up to the client
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+            .filter(|_| local);
+        match forwarded.or(peer.0) {
+            Some(ip) => Self::from_addr(ip),
+            None => Self("local".to_owned()),
+        }
+    }
+
+    pub fn from_addr(ip: IpAddr) -> Self {
+        Self(ip.to_string())
+    }
+
+    /// Chat user voting through chat bridge, told apart by host of user connection.
+    pub fn chat(host: &str) -> Self {
+        Self(format!("chat:{host}"))
+    }
+}
+
+/// Number of distinct viewers and active voters.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, serde::Serialize)]
+pub struct Audience {
+    pub viewers: usize,
+    pub voters: usize,
+}
+
+impl std::fmt::Display for Audience {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Viewers: {}, voters: {}", self.viewers, self.voters)
+    }
+}
+
+/// Recently seen viewers and voters.
+#[derive(Default)]
+pub struct Presence {
+    /// last game page view of client
+    page_views: HashMap<Client, Instant>,
+    /// number of open game streams of client
+    streams: HashMap<Client, usize>,
+    /// last accepted vote of client
+    votes: HashMap<Client, Instant>,
+    /// clients with votes pending for next tick
+    tick_voters: HashSet<Client>,
+}
+
+impl Presence {
+    pub fn viewed(&mut self, client: &Client, now: Instant) {
+        self.page_views.insert(client.clone(), now);
+    }
+
+    pub fn stream_opened(&mut self, client: &Client) {
+        *self.streams.entry(client.clone()).or_default() += 1;
+    }
+
+    pub fn stream_closed(&mut self, client: &Client) {
+        if let Some(count) = self.streams.get_mut(client) {
+            *count -= 1;
+            if *count == 0 {
+                self.streams.remove(client);
+            }
+        }
+    }
+
+    pub fn voted(&mut self, client: &Client, now: Instant) {
+        self.votes.insert(client.clone(), now);
+        self.tick_voters.insert(client.clone());
+    }
+
+    /// Forget voters of pending votes, when votes are dropped.
+    pub fn clear_tick_voters(&mut self) {
+        self.tick_voters.clear();
+    }
+
+    /// Turnout of votes collected for tick, starts collecting voters for next one.
+    pub fn end_tick(&mut self, now: Instant) -> Turnout {
+        let voters = self.tick_voters.len();
+        self.tick_voters.clear();
+        Turnout {
+            voters,
+            active: self.audience(now).voters,
+        }
+    }
+
+    /// Current audience, clients not seen within their window are forgotten.
+    pub fn audience(&mut self, now: Instant) -> Audience {
+        self.page_views
+            .retain(|_, seen| now.duration_since(*seen) < VIEWER_WINDOW);
+        self.votes
+            .retain(|_, seen| now.duration_since(*seen) < VOTER_WINDOW);
+
+        let streaming = self
+            .streams
+            .keys()
+            .filter(|c| !self.page_views.contains_key(*c))
+            .count();
+        Audience {
+            viewers: self.page_views.len() + streaming,
+            voters: self.votes.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Audience, Client, Presence, VIEWER_WINDOW, VOTER_WINDOW};
+    use crate::listen::PeerAddr;
+    use axum::http::HeaderMap;
+    use std::time::{Duration, Instant};
+
+    fn client(ip: &str) -> Client {
+        Client::identify(None, &HeaderMap::new(), PeerAddr(Some(ip.parse().unwrap())))
+    }
+
+    #[test]
+    fn forwarded_address_is_trusted_from_local_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 192.0.2.7".parse().unwrap());
+
+        let proxied = Client::identify(None, &headers, PeerAddr(None));
+        assert_eq!(proxied, client("192.0.2.7"));
+        let direct = Client::identify(None, &headers, PeerAddr(Some("192.0.2.9".parse().unwrap())));
+        assert_eq!(direct, client("192.0.2.9"));
+
+        let token = Client::identify(Some("secret"), &headers, PeerAddr(None));
+        assert_ne!(token, proxied);
+        assert!(!format!("{token:?}").contains("secret"));
+    }
+
+    #[test]
+    fn viewers_and_voters_are_counted_once_within_window() {
+        let mut presence = Presence::default();
+        let start = Instant::now();
+        let (a, b) = (client("192.0.2.1"), client("192.0.2.2"));
+
+        presence.viewed(&a, start);
+        presence.viewed(&a, start);
+        presence.stream_opened(&a);
+        presence.stream_opened(&b);
+        presence.voted(&a, start);
+        presence.voted(&a, start);
+        assert_eq!(
+            presence.audience(start),
+            Audience {
+                viewers: 2,
+                voters: 1
+            }
+        );
+
+        let turnout = presence.end_tick(start + VOTER_WINDOW / 2);
+        assert_eq!((turnout.voters, turnout.active), (1, 1));
+        assert_eq!(presence.end_tick(start + VOTER_WINDOW).voters, 0);
+
+        presence.stream_closed(&b);
+        let later = start + VIEWER_WINDOW + Duration::from_secs(1);
+        assert_eq!(
+            presence.audience(later),
+            Audience {
+                viewers: 1,
+                voters: 0
+            }
+        );
+    }
+}
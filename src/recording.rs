@@ -0,0 +1,292 @@
+use axum::body::Bytes;
+use snapi::snake_game::{renderer::TileFrame, TileChange, TileType};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use tokio::sync::OnceCell;
+
+/// size of single level tile in output image (in pixels)
+const TILE_PIXELS: usize = 8;
+
+// palette indexes
+const COLOR_EMPTY: u8 = 0;
+const COLOR_SNAKE: u8 = 1;
+const COLOR_FRUIT: u8 = 2;
+const COLOR_WALL: u8 = 3;
+const PALETTE: [u8; 12] = [
+    0x00, 0x00, 0x00, // empty - black
+    0xFF, 0xFF, 0xFF, // snake - white
+    0xE0, 0x20, 0x20, // fruit - red
+    0x60, 0x60, 0x60, // wall - gray
+];
+
+/// Keeps up to `capacity` latest frames, dropping the oldest ones.
+/// Frames are stored as tiles changed since previous frame, only the oldest frame and frames
+/// of new levels are kept whole.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    capacity: usize,
+    /// first frame is always whole
+    frames: VecDeque<RecordedFrame>,
+    /// latest frame with all changes applied
+    last: Option<TileFrame>,
+}
+
+#[derive(Clone)]
+enum RecordedFrame {
+    Whole(TileFrame),
+    Changes(Vec<TileChange>),
+}
+
+impl FrameBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            frames: VecDeque::new(),
+            last: None,
+        }
+    }
+
+    /// Whole frame, e.g. first frame of new game.
+    pub fn push(&mut self, frame: TileFrame) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.last = Some(frame.clone());
+        self.push_recorded(RecordedFrame::Whole(frame));
+    }
+
+    /// Frame made of tiles changed since the last pushed frame.
+    pub fn push_changes(&mut self, changes: Vec<TileChange>) {
+        // nothing to apply changes to
+        let Some(last) = &mut self.last else {
+            return;
+        };
+        last.apply(&changes);
+        self.push_recorded(RecordedFrame::Changes(changes));
+    }
+
+    fn push_recorded(&mut self, frame: RecordedFrame) {
+        if self.frames.len() == self.capacity {
+            self.drop_oldest();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Oldest frame is dropped, the following one is made whole.
+    fn drop_oldest(&mut self) {
+        let Some(RecordedFrame::Whole(mut oldest)) = self.frames.pop_front() else {
+            return;
+        };
+        if let Some(next) = self.frames.front_mut() {
+            if let RecordedFrame::Changes(changes) = next {
+                oldest.apply(changes);
+                *next = RecordedFrame::Whole(oldest);
+            }
+        }
+    }
+
+    pub fn last(&self) -> Option<&TileFrame> {
+        self.last.as_ref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Kept frames from the oldest one, each is rebuilt when iterated.
+    pub fn frames(&self) -> impl Iterator<Item = TileFrame> + '_ {
+        let mut current: Option<TileFrame> = None;
+        self.frames.iter().filter_map(move |frame| {
+            match (frame, &mut current) {
+                (RecordedFrame::Whole(frame), _) => current = Some(frame.clone()),
+                (RecordedFrame::Changes(changes), Some(current)) => current.apply(changes),
+                (RecordedFrame::Changes(_), None) => return None,
+            }
+            current.clone()
+        })
+    }
+}
+
+/// Frames of finished game, they do not change so GIF is encoded only once.
+pub struct Replay {
+    pub frames: FrameBuffer,
+    /// encoded on first request, concurrent requests wait for it
+    pub gif: OnceCell<Bytes>,
+}
+
+impl Replay {
+    pub fn new(frames: FrameBuffer) -> Self {
+        Self {
+            frames,
+            gif: OnceCell::new(),
+        }
+    }
+}
+
+/// Encode frames as looped animated gif, `frame_delay` is in hundredths of a second.
+pub fn encode_gif(
+    frames: impl IntoIterator<Item = TileFrame>,
+    frame_delay: u16,
+) -> Result<Vec<u8>, gif::EncodingError> {
+    let mut output = Vec::new();
+
+    let mut frames = frames.into_iter().peekable();
+    let (width, height) = match frames.peek() {
+        Some(frame) => (
+            frame.topology.text_columns(frame.width) * column_pixels(frame),
+            frame.height * TILE_PIXELS,
+        ),
+        None => return Ok(output),
+    };
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("frame of {width}x{height} pixels is too large for gif"),
+            )
+            .into())
+        }
+    };
+
+    {
+        let mut encoder = gif::Encoder::new(&mut output, width, height, &PALETTE)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        for frame in frames {
+            let mut gif_frame = gif::Frame {
+                width,
+                height,
+                delay: frame_delay,
+                ..Default::default()
+            };
+            gif_frame.buffer = Cow::Owned(frame_pixels(&frame));
+            encoder.write_frame(&gif_frame)?;
+        }
+        // drop encoder - writes gif trailer
+    }
+
+    Ok(output)
+}
+
+/// Width of text column of frame topology in pixels, each tile takes `TILE_PIXELS`.
+fn column_pixels(frame: &TileFrame) -> usize {
+    TILE_PIXELS / frame.topology.cell_width()
+}
+
+fn frame_pixels(frame: &TileFrame) -> Vec<u8> {
+    let column_pixels = column_pixels(frame);
+    let row_pixels = frame.topology.text_columns(frame.width) * column_pixels;
+    let mut pixels = Vec::with_capacity(row_pixels * frame.height * TILE_PIXELS);
+
+    for (y, row) in frame.tiles.chunks(frame.width).enumerate() {
+        let mut line = vec![COLOR_EMPTY; row_pixels];
+        for (x, tile) in row.iter().enumerate() {
+            let color = match tile {
+                TileType::Empty => COLOR_EMPTY,
+                TileType::Snake => COLOR_SNAKE,
+                TileType::Fruit => COLOR_FRUIT,
+                TileType::Wall => COLOR_WALL,
+            };
+            // tiles of shifted rows (hex grid) are offset by text column
+            let start = frame.topology.text_cell(x, y).0 * column_pixels;
+            line[start..start + TILE_PIXELS].fill(color);
+        }
+        // scale row vertically
+        for _ in 0..TILE_PIXELS {
+            pixels.extend_from_slice(&line);
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_gif, FrameBuffer};
+    use snapi::snake_game::{renderer::TileFrame, topology, TileChange, TileType};
+
+    fn frame(tiles: Vec<TileType>) -> TileFrame {
+        TileFrame {
+            width: 2,
+            height: 2,
+            tiles,
+            topology: topology::by_name("square").unwrap(),
+        }
+    }
+
+    #[test]
+    fn frame_buffer_keeps_latest_frames() {
+        use TileType::*;
+
+        let mut buffer = FrameBuffer::new(2);
+        buffer.push(frame(vec![Snake, Empty, Empty, Empty]));
+        buffer.push(frame(vec![Empty, Snake, Empty, Empty]));
+        buffer.push(frame(vec![Empty, Empty, Snake, Empty]));
+
+        let frames = buffer.frames().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].tiles[1], Snake);
+        assert_eq!(frames[1].tiles[2], Snake);
+    }
+
+    #[test]
+    fn frame_changes_are_kept_after_oldest_frame_is_dropped() {
+        use TileType::*;
+
+        let change = |x, y, tile| TileChange { x, y, tile };
+        let mut buffer = FrameBuffer::new(2);
+        buffer.push(frame(vec![Snake, Empty, Empty, Empty]));
+        buffer.push_changes(vec![change(0, 0, Empty), change(1, 0, Snake)]);
+        buffer.push_changes(vec![change(1, 0, Empty), change(1, 1, Snake)]);
+
+        let frames = buffer.frames().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].tiles, vec![Empty, Snake, Empty, Empty]);
+        assert_eq!(frames[1].tiles, vec![Empty, Empty, Empty, Snake]);
+        assert_eq!(buffer.last().unwrap().tiles, frames[1].tiles);
+    }
+
+    #[test]
+    fn gif_encoding() {
+        use TileType::*;
+
+        let frames = vec![
+            frame(vec![Snake, Empty, Fruit, Empty]),
+            frame(vec![Empty, Snake, Fruit, Empty]),
+        ];
+        let output = encode_gif(frames, 20).unwrap();
+
+        assert!(output.starts_with(b"GIF89a"));
+        assert_eq!(output.last(), Some(&0x3B)); // gif trailer
+    }
+
+    #[test]
+    fn too_large_frame_is_not_encoded() {
+        let frame = TileFrame {
+            width: 10_000,
+            height: 1,
+            ..frame(vec![TileType::Empty; 10_000])
+        };
+        assert!(encode_gif(vec![frame], 20).is_err());
+    }
+
+    #[test]
+    fn hex_frame_rows_are_shifted() {
+        use TileType::*;
+
+        let frame = TileFrame {
+            topology: topology::by_name("hex").unwrap(),
+            ..frame(vec![Snake, Empty, Snake, Empty])
+        };
+        let pixels = super::frame_pixels(&frame);
+        let row_pixels = 2 * super::TILE_PIXELS + super::TILE_PIXELS / 2;
+        assert_eq!(pixels.len(), row_pixels * 2 * super::TILE_PIXELS);
+
+        let first_row = &pixels[..row_pixels];
+        let second_row = &pixels[row_pixels * super::TILE_PIXELS..][..row_pixels];
+        assert_eq!(first_row[0], super::COLOR_SNAKE);
+        assert_eq!(second_row[0], super::COLOR_EMPTY);
+        assert_eq!(second_row[super::TILE_PIXELS / 2], super::COLOR_SNAKE);
+    }
+}
@@ -0,0 +1,104 @@
+use snapi::snake_game::DeathCause;
+use std::collections::{HashMap, VecDeque};
+
+/// number of latest games kept in history
+const HISTORY_SIZE: usize = 100;
+
+#[derive(Clone, serde::Serialize)]
+pub struct GameScore {
+    pub ticks: u64,
+    pub length: usize,
+    /// `None` when game was stopped by error
+    pub death: Option<DeathCause>,
+    /// name of bot driving the snake when game ended
+    pub bot: Option<String>,
+    /// all win conditions were met or snake filled whole level
+    pub won: bool,
+    /// name of played puzzle
+    pub puzzle: Option<String>,
+}
+
+#[derive(Clone, Default, serde::Serialize)]
+pub struct BotResults {
+    pub games: usize,
+    pub best_length: usize,
+    pub total_length: usize,
+    pub total_ticks: u64,
+}
+
+#[derive(Default)]
+pub struct ScoreHistory {
+    games: VecDeque<GameScore>,
+    bots: HashMap<String, BotResults>,
+    /// longest snake since server start
+    best_length: Option<usize>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ScoresView {
+    /// latest games first
+    pub games: Vec<GameScore>,
+    pub bots: HashMap<String, BotResults>,
+}
+
+impl ScoreHistory {
+    pub fn record(&mut self, score: GameScore) {
+        self.best_length = self.best_length.max(Some(score.length));
+        if let Some(bot) = &score.bot {
+            let results = self.bots.entry(bot.to_owned()).or_default();
+            results.games += 1;
+            results.best_length = results.best_length.max(score.length);
+            results.total_length += score.length;
+            results.total_ticks += score.ticks;
+        }
+
+        if self.games.len() == HISTORY_SIZE {
+            self.games.pop_back();
+        }
+        self.games.push_front(score);
+    }
+
+    pub fn best_length(&self) -> Option<usize> {
+        self.best_length
+    }
+
+    pub fn view(&self) -> ScoresView {
+        ScoresView {
+            games: self.games.iter().cloned().collect(),
+            bots: self.bots.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeathCause, GameScore, ScoreHistory};
+
+    #[test]
+    fn bot_results_are_accumulated() {
+        let mut history = ScoreHistory::default();
+        let score = |length, bot: Option<&str>| GameScore {
+            ticks: 10,
+            length,
+            death: Some(DeathCause::ReversedIntoItself),
+            bot: bot.map(String::from),
+            won: false,
+            puzzle: None,
+        };
+
+        history.record(score(5, Some("alpha")));
+        history.record(score(3, None));
+        history.record(score(8, Some("alpha")));
+
+        assert_eq!(history.best_length(), Some(8));
+        let view = history.view();
+        assert_eq!(view.games.len(), 3);
+        assert_eq!(view.games[0].length, 8);
+
+        let alpha = &view.bots["alpha"];
+        assert_eq!(alpha.games, 2);
+        assert_eq!(alpha.best_length, 8);
+        assert_eq!(alpha.total_length, 13);
+        assert_eq!(alpha.total_ticks, 20);
+    }
+}
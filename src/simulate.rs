@@ -0,0 +1,266 @@
+use snapi::snake_game::{
+    controller,
+    topology::{self, Boundary},
+    GameBuilder, GameError, GameOutcome, MovementDirection,
+};
+use std::collections::BTreeMap;
+
+const USAGE: &str = "Usage: snapi simulate [options]
+
+Options:
+    --controller <name>     one of: random, greedy, path, hamiltonian (default: path)
+    --games <count>         number of simulated games (default: 1000)
+    --seed <seed>           first seed, game n uses seed + n (default: 0)
+    --width <width>         level width (default: 40)
+    --height <height>       level height (default: 20)
+    --tail <size>           initial snake tail size (default: 2)
+    --topology <name>       one of: square, square8, hex (default: square)
+    --boundary <name>       one of: wrap, solid, mobius, klein (default: wrap)
+    --max-fruits <count>    max fruits on level (default: 5)
+    --fruit-chance <chance> chance of new fruit on each move, 0.01 - 1.00 (default: 0.1)
+    --max-ticks <ticks>     game is stopped after given ticks (default: 10000)
+    --format <format>       output format: csv, json (default: csv)
+";
+
+struct Options {
+    controller: String,
+    games: u64,
+    seed: u64,
+    width: usize,
+    height: usize,
+    tail_size: usize,
+    topology: String,
+    boundary: Boundary,
+    max_fruits: usize,
+    fruit_chance: f64,
+    max_ticks: u64,
+    format: Format,
+}
+
+enum Format {
+    Csv,
+    Json,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            controller: "path".to_owned(),
+            games: 1000,
+            seed: 0,
+            width: crate::LEVEL_WIDTH,
+            height: crate::LEVEL_HEIGHT,
+            tail_size: crate::SNAKE_TAIL_SIZE,
+            topology: crate::TOPOLOGY.to_owned(),
+            boundary: Boundary::Wrap,
+            max_fruits: crate::MAX_FRUITS,
+            fruit_chance: crate::NEW_FRUIT_CHANCE,
+            max_ticks: 10_000,
+            format: Format::Csv,
+        }
+    }
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {arg}"))?;
+            let invalid = || format!("Invalid value for {arg}: {value}");
+
+            match arg.as_str() {
+                "--controller" => {
+                    if !controller::CONTROLLERS.contains(&value.as_str()) {
+                        return Err(invalid());
+                    }
+                    options.controller = value;
+                }
+                "--games" => options.games = value.parse().map_err(|_| invalid())?,
+                "--seed" => options.seed = value.parse().map_err(|_| invalid())?,
+                "--width" => options.width = value.parse().map_err(|_| invalid())?,
+                "--height" => options.height = value.parse().map_err(|_| invalid())?,
+                "--tail" => options.tail_size = value.parse().map_err(|_| invalid())?,
+                "--topology" => {
+                    if !topology::TOPOLOGIES.contains(&value.as_str()) {
+                        return Err(invalid());
+                    }
+                    options.topology = value;
+                }
+                "--boundary" => options.boundary = value.parse().map_err(|_| invalid())?,
+                "--max-fruits" => options.max_fruits = value.parse().map_err(|_| invalid())?,
+                "--fruit-chance" => {
+                    options.fruit_chance = value.parse().map_err(|_| invalid())?;
+                    if !(0.01..=1.0).contains(&options.fruit_chance) {
+                        return Err(invalid());
+                    }
+                }
+                "--max-ticks" => options.max_ticks = value.parse().map_err(|_| invalid())?,
+                "--format" => {
+                    options.format = match value.as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(format!("Unknown option: {arg}")),
+            }
+        }
+        // game n uses seed + n
+        if options.seed.checked_add(options.games).is_none() {
+            return Err(format!(
+                "Seed {} is too large for {} games",
+                options.seed, options.games
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+struct GameResult {
+    length: usize,
+    ticks: u64,
+    cause: &'static str,
+}
+
+#[derive(Default, serde::Serialize)]
+struct Statistics {
+    games: u64,
+    mean_length: f64,
+    max_length: usize,
+    mean_ticks: f64,
+    max_ticks: u64,
+    /// games count by game end cause
+    causes: BTreeMap<&'static str, u64>,
+}
+
+impl Statistics {
+    fn add(&mut self, result: &GameResult) {
+        self.games += 1;
+        self.mean_length += (result.length as f64 - self.mean_length) / self.games as f64;
+        self.mean_ticks += (result.ticks as f64 - self.mean_ticks) / self.games as f64;
+        self.max_length = self.max_length.max(result.length);
+        self.max_ticks = self.max_ticks.max(result.ticks);
+        *self.causes.entry(result.cause).or_default() += 1;
+    }
+
+    fn to_csv(&self) -> String {
+        let mut output = format!(
+            "metric,value\ngames,{}\nmean_length,{:.2}\nmax_length,{}\nmean_ticks,{:.2}\nmax_ticks,{}\n",
+            self.games, self.mean_length, self.max_length, self.mean_ticks, self.max_ticks
+        );
+        for (cause, count) in self.causes.iter() {
+            output.push_str(&format!("cause_{cause},{count}\n"));
+        }
+        output
+    }
+}
+
+fn simulate_game(options: &Options, seed: u64) -> Result<GameResult, GameError> {
+    let topology = topology::by_name(&options.topology)
+        .ok_or(GameError::InvalidInternalState("unknown topology"))?;
+    let mut game = GameBuilder::new(options.width, options.height)
+        .topology(topology)
+        .boundary(options.boundary)
+        .direction(MovementDirection::Right)
+        .tail_size(options.tail_size)
+        .random_fruits(options.max_fruits, options.fruit_chance)
+        .seed(seed)
+        .build()?;
+
+    let mut controller = controller::by_name(&options.controller, Some(seed))
+        .ok_or(GameError::InvalidInternalState("unknown controller"))?;
+
+    let mut ticks = 0;
+    let cause = loop {
+        if ticks == options.max_ticks {
+            break "tick_limit";
+        }
+        ticks += 1;
+
+        let direction = controller.next_direction(&game);
+        let result = game
+            .set_snake_direction(direction)
+            .and_then(|_| game.try_move());
+        match result {
+            Ok(GameOutcome::Running) => (),
+            Ok(GameOutcome::Won) => break "won",
+            Ok(GameOutcome::BoardFull) => break "board_full",
+            Err(GameError::GameOver(cause)) => break cause.name(),
+            Err(err) => return Err(err),
+        }
+    };
+
+    Ok(GameResult {
+        length: game.snake().len(),
+        ticks,
+        cause,
+    })
+}
+
+/// Run `simulate` subcommand with given arguments (after subcommand name).
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let args = args.collect::<Vec<_>>();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        print!("{USAGE}");
+        return Ok(());
+    }
+
+    let options = Options::parse(args.into_iter()).map_err(|err| format!("{err}\n\n{USAGE}"))?;
+    if options.width < 1 || options.height < 1 || options.tail_size < 1 {
+        return Err(format!("Invalid level or snake size\n\n{USAGE}"));
+    }
+
+    let mut statistics = Statistics::default();
+    for seed in options.seed..options.seed + options.games {
+        let result = simulate_game(&options, seed).map_err(|err| format!("Seed {seed}: {err}"))?;
+        statistics.add(&result);
+    }
+
+    match options.format {
+        Format::Csv => print!("{}", statistics.to_csv()),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&statistics).map_err(|err| err.to_string())?
+        ),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+
+    #[test]
+    fn simulation_is_repeatable_for_seed() {
+        let args = [
+            "--games", "3", "--seed", "7", "--width", "10", "--height", "6",
+        ];
+        let options = Options::parse(args.iter().map(|a| a.to_string())).unwrap();
+
+        for seed in 7..10 {
+            let first = super::simulate_game(&options, seed).unwrap();
+            let second = super::simulate_game(&options, seed).unwrap();
+            assert_eq!(first.length, second.length);
+            assert_eq!(first.ticks, second.ticks);
+        }
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let parse = |args: &[&str]| Options::parse(args.iter().map(|a| a.to_string()));
+
+        assert!(parse(&["--controller", "unknown"]).is_err());
+        assert!(parse(&["--fruit-chance", "2"]).is_err());
+        assert!(parse(&["--games"]).is_err());
+        assert!(parse(&["--seed", &u64::MAX.to_string()]).is_err());
+        assert!(parse(&["--seed", &u64::MAX.to_string(), "--games", "0"]).is_ok());
+        assert!(parse(&["--format", "json"]).is_ok());
+        assert!(parse(&["--topology", "triangle"]).is_err());
+        assert!(parse(&["--boundary", "mobius", "--topology", "hex"]).is_ok());
+    }
+}
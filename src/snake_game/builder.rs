@@ -0,0 +1,341 @@
+use super::{
+    fruit::FruitRandomLimited,
+    scenario::WinCondition,
+    snake::{Boundary, Snake},
+    topology::{Square, Topology},
+    FruitBehavior, Game, GameError, GameLevel, LevelError, MovementDirection, SnakeBehavior,
+    TileType,
+};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+const DEFAULT_TAIL_SIZE: usize = 2;
+const DEFAULT_MAX_FRUITS: usize = 3;
+const DEFAULT_FRUIT_CHANCE: f64 = 0.1;
+
+/// Reason of rejected game setup.
+#[derive(Debug, Eq, PartialEq)]
+pub enum BuildError {
+    InvalidLevel(LevelError),
+    /// level is too small for snake put on level center
+    LevelTooSmall {
+        min_width: usize,
+        min_height: usize,
+    },
+    /// snake needs head and at least one tail tile
+    SnakeTooShort,
+    /// position is outside of level
+    OutOfBounds {
+        x: usize,
+        y: usize,
+    },
+    /// more than one object was placed on position
+    Overlap {
+        x: usize,
+        y: usize,
+    },
+    /// snake segment on position is not next to previous one
+    Disconnected {
+        x: usize,
+        y: usize,
+    },
+    /// snake direction points to its own neck
+    FacingTail,
+    /// snake direction is not supported by level topology
+    UnsupportedDirection(MovementDirection),
+    /// level edges do not fit level topology and height, see [`Boundary::fits`]
+    UnsupportedBoundary(Boundary),
+    /// snake could not be placed on level center
+    Placement(&'static str),
+    /// scenario level map could not be read
+    InvalidMap(&'static str),
+}
+
+/// Game setup with exact starting positions.
+///
+/// Without explicit snake path, snake is put on level center like with [`Game::put_snake`].
+pub struct GameBuilder<F = FruitRandomLimited> {
+    width: usize,
+    height: usize,
+    direction: MovementDirection,
+    /// snake tiles positions (head first)
+    snake: Option<Vec<(usize, usize)>>,
+    tail_size: usize,
+    fruits: Vec<(usize, usize)>,
+    walls: Vec<(usize, usize)>,
+    boundary: Boundary,
+    topology: Arc<dyn Topology>,
+    win_conditions: Vec<WinCondition>,
+    fruit: F,
+    fruit_limit: usize,
+    fruit_chance: f64,
+    seed: Option<u64>,
+}
+
+impl GameBuilder {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            direction: MovementDirection::Right,
+            snake: None,
+            tail_size: DEFAULT_TAIL_SIZE,
+            fruits: Vec::new(),
+            walls: Vec::new(),
+            boundary: Boundary::Wrap,
+            topology: Arc::new(Square),
+            win_conditions: Vec::new(),
+            fruit: FruitRandomLimited::new(DEFAULT_MAX_FRUITS, DEFAULT_FRUIT_CHANCE),
+            fruit_limit: DEFAULT_MAX_FRUITS,
+            fruit_chance: DEFAULT_FRUIT_CHANCE,
+            seed: None,
+        }
+    }
+
+    /// Fruits are put randomly, up to `limit` fruits on level.
+    pub fn random_fruits(mut self, limit: usize, chance: f64) -> Self {
+        self.fruit_limit = limit;
+        self.fruit_chance = chance;
+        self.reset_random_fruits()
+    }
+
+    /// Make random fruits placement repeatable.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self.reset_random_fruits()
+    }
+
+    fn reset_random_fruits(mut self) -> Self {
+        self.fruit = match self.seed {
+            Some(seed) => FruitRandomLimited::seeded(self.fruit_limit, self.fruit_chance, seed),
+            None => FruitRandomLimited::new(self.fruit_limit, self.fruit_chance),
+        };
+        self
+    }
+}
+
+impl<F: FruitBehavior> GameBuilder<F> {
+    /// Replace fruits policy.
+    pub fn fruit_policy<G: FruitBehavior>(self, fruit: G) -> GameBuilder<G> {
+        GameBuilder {
+            width: self.width,
+            height: self.height,
+            direction: self.direction,
+            snake: self.snake,
+            tail_size: self.tail_size,
+            fruits: self.fruits,
+            walls: self.walls,
+            boundary: self.boundary,
+            topology: self.topology,
+            win_conditions: self.win_conditions,
+            fruit,
+            fruit_limit: self.fruit_limit,
+            fruit_chance: self.fruit_chance,
+            seed: self.seed,
+        }
+    }
+
+    /// Initial movement direction of snake.
+    pub fn direction(mut self, direction: MovementDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Tail size of snake put on level center.
+    pub fn tail_size(mut self, tail_size: usize) -> Self {
+        self.tail_size = tail_size;
+        self
+    }
+
+    /// Put snake on given positions (head first), successive positions must be next to each other.
+    pub fn snake(mut self, direction: MovementDirection, body: &[(usize, usize)]) -> Self {
+        self.direction = direction;
+        self.snake = Some(body.to_vec());
+        self
+    }
+
+    pub fn fruit(mut self, x: usize, y: usize) -> Self {
+        self.fruits.push((x, y));
+        self
+    }
+
+    pub fn wall(mut self, x: usize, y: usize) -> Self {
+        self.walls.push((x, y));
+        self
+    }
+
+    pub fn walls(mut self, positions: &[(usize, usize)]) -> Self {
+        self.walls.extend_from_slice(positions);
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    /// Level grid shape, square grid by default.
+    pub fn topology(mut self, topology: Arc<dyn Topology>) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Conditions to win the game, pre-placed fruits are the ones to be eaten.
+    pub fn win_conditions(mut self, conditions: Vec<WinCondition>) -> Self {
+        self.win_conditions = conditions;
+        self
+    }
+
+    pub fn build(self) -> Result<Game<Snake, F>, BuildError> {
+        let mut level =
+            GameLevel::try_with_topology(self.width, self.height, self.topology.clone())
+                .map_err(BuildError::InvalidLevel)?;
+        if !level.topology().supports(self.direction) {
+            return Err(BuildError::UnsupportedDirection(self.direction));
+        }
+        if !self.boundary.fits(level.topology(), self.height) {
+            return Err(BuildError::UnsupportedBoundary(self.boundary));
+        }
+        if self.snake.is_none() {
+            if self.tail_size < 1 {
+                return Err(BuildError::SnakeTooShort);
+            }
+            let min = Snake::min_level_size(self.tail_size, self.direction);
+            if self.width < min.width || self.height < min.height {
+                return Err(BuildError::LevelTooSmall {
+                    min_width: min.width,
+                    min_height: min.height,
+                });
+            }
+        }
+
+        for (positions, tile_type) in [
+            (&self.walls, TileType::Wall),
+            (&self.fruits, TileType::Fruit),
+        ] {
+            for &(x, y) in positions {
+                let tile = level
+                    .get_tile_on(x, y)
+                    .ok_or(BuildError::OutOfBounds { x, y })?;
+                if tile.tile_type() != TileType::Empty {
+                    return Err(BuildError::Overlap { x, y });
+                }
+                level.set_tile_on(x, y, tile_type);
+            }
+        }
+
+        let mut snake = Snake::with_boundary(self.direction, self.boundary);
+        match &self.snake {
+            Some(body) => {
+                let path = self.snake_path(&level, body)?;
+                snake
+                    .put_on_path(&mut level, &path)
+                    .map_err(placement_error)?;
+            }
+            None => {
+                snake
+                    .put_on(&mut level, self.tail_size)
+                    .map_err(placement_error)?;
+            }
+        }
+
+        let mut game = Game::new(level, snake, self.fruit);
+        game.set_win_conditions(self.win_conditions);
+        Ok(game)
+    }
+
+    /// Validate snake body and convert it to tile indexes.
+    fn snake_path(
+        &self,
+        level: &GameLevel,
+        body: &[(usize, usize)],
+    ) -> Result<Vec<usize>, BuildError> {
+        if body.len() < 2 {
+            return Err(BuildError::SnakeTooShort);
+        }
+
+        let mut path: Vec<usize> = Vec::with_capacity(body.len());
+        for (i, &(x, y)) in body.iter().enumerate() {
+            let tile = level
+                .get_tile_on(x, y)
+                .ok_or(BuildError::OutOfBounds { x, y })?;
+            if tile.tile_type() != TileType::Empty || path.contains(&tile.get_index()) {
+                return Err(BuildError::Overlap { x, y });
+            }
+            if i > 0 && !self.is_next_to(level, path[i - 1], tile.get_index()) {
+                return Err(BuildError::Disconnected { x, y });
+            }
+            path.push(tile.get_index());
+        }
+
+        if self.step(level, path[0], self.direction) == Some(path[1]) {
+            return Err(BuildError::FacingTail);
+        }
+
+        Ok(path)
+    }
+
+    /// Index of tile next to given one in direction, respecting boundary policy.
+    fn step(&self, level: &GameLevel, index: usize, direction: MovementDirection) -> Option<usize> {
+        let tile = level.get_tile(index)?;
+        level
+            .neighbour(&tile, direction, self.boundary)
+            .map(|(tile, _)| tile.get_index())
+    }
+
+    fn is_next_to(&self, level: &GameLevel, index: usize, other: usize) -> bool {
+        level
+            .topology()
+            .directions()
+            .iter()
+            .any(|d| self.step(level, index, *d) == Some(other))
+    }
+}
+
+fn placement_error(err: GameError) -> BuildError {
+    match err {
+        GameError::InvalidInternalState(description) => BuildError::Placement(description),
+        _ => BuildError::Placement("snake could not be placed"),
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLevel(err) => write!(f, "{err}"),
+            Self::LevelTooSmall {
+                min_width,
+                min_height,
+            } => write!(f, "level must be at least {min_width}x{min_height}"),
+            Self::SnakeTooShort => write!(f, "snake needs head and at least one tail tile"),
+            Self::OutOfBounds { x, y } => write!(f, "position ({x}, {y}) is out of level"),
+            Self::Overlap { x, y } => write!(f, "position ({x}, {y}) is already occupied"),
+            Self::Disconnected { x, y } => {
+                write!(f, "snake segment at ({x}, {y}) is not next to previous one")
+            }
+            Self::FacingTail => write!(f, "snake direction points to its tail"),
+            Self::UnsupportedDirection(direction) => {
+                write!(
+                    f,
+                    "direction {direction:?} is not supported by level topology"
+                )
+            }
+            Self::UnsupportedBoundary(boundary) => write!(
+                f,
+                "{} level edges are not supported by level topology with this height",
+                boundary.name()
+            ),
+            Self::Placement(description) => write!(f, "snake placement failed - {description}"),
+            Self::InvalidMap(description) => write!(f, "invalid level map - {description}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidLevel(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use super::{
+    topology::Boundary, FruitBehavior, Game, GameLevel, MovementDirection, SnakeBehavior, TileType,
+    TileXY,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::collections::VecDeque;
+
+/// Produces snake movement for each game tick.
+pub trait Controller<S: SnakeBehavior, F: FruitBehavior> {
+    fn next_direction(&mut self, game: &Game<S, F>) -> MovementDirection;
+}
+
+/// Names of built-in controllers.
+pub const CONTROLLERS: [&str; 4] = ["random", "greedy", "path", "hamiltonian"];
+
+/// Built-in controller by its name, `seed` makes random decisions repeatable.
+pub fn by_name<S: SnakeBehavior, F: FruitBehavior>(
+    name: &str,
+    seed: Option<u64>,
+) -> Option<Box<dyn Controller<S, F> + Send>> {
+    match name {
+        "random" => Some(Box::new(match seed {
+            Some(seed) => RandomSafe::seeded(seed),
+            None => RandomSafe::new(),
+        })),
+        "greedy" => Some(Box::new(GreedyFruit)),
+        "path" => Some(Box::new(ShortestPath)),
+        "hamiltonian" => Some(Box::new(HamiltonianCycle::default())),
+        _ => None,
+    }
+}
+
+/// Position of tile next to given one, level edges are handled by snake boundary policy.
+/// `None` when direction is not supported by level topology or step hits solid level edge.
+fn step(
+    level: &GameLevel,
+    boundary: Boundary,
+    from: &TileXY,
+    direction: MovementDirection,
+) -> Option<TileXY> {
+    let tile = level.get_tile_on(from.x, from.y)?;
+    level
+        .neighbour(&tile, direction, boundary)
+        .map(|(tile, _)| level.get_tile_position(&tile))
+}
+
+fn tile_type_on(level: &GameLevel, position: &TileXY) -> Option<TileType> {
+    level
+        .get_tile_on(position.x, position.y)
+        .map(|t| t.tile_type())
+}
+
+fn is_safe(level: &GameLevel, position: Option<&TileXY>) -> bool {
+    !matches!(
+        position.and_then(|p| tile_type_on(level, p)),
+        Some(TileType::Snake | TileType::Wall) | None
+    )
+}
+
+fn head_position(level: &GameLevel, snake: &dyn SnakeBehavior) -> Option<TileXY> {
+    let head = *snake.tiles().first()?;
+    level.get_tile(head).map(|t| level.get_tile_position(&t))
+}
+
+/// Directions snake may turn to (without reversing) which does not lead into snake.
+fn safe_directions(level: &GameLevel, snake: &dyn SnakeBehavior) -> Vec<MovementDirection> {
+    let current = snake.direction();
+    let head = match head_position(level, snake) {
+        Some(head) => head,
+        None => return Vec::new(),
+    };
+
+    level
+        .topology()
+        .directions()
+        .iter()
+        .copied()
+        .filter(|d| !d.is_opposite_to(&current))
+        .filter(|d| is_safe(level, step(level, snake.boundary(), &head, *d).as_ref()))
+        .collect()
+}
+
+/// Distance between tiles, shortest way may lead over level edges joined by boundary policy.
+/// Approximate for hex grids.
+fn distance(level: &GameLevel, boundary: Boundary, a: &TileXY, b: &TileXY) -> usize {
+    let d = level.level_dimensions();
+    let axes = |dx: usize, dy: usize| {
+        if level.topology().supports(MovementDirection::UpLeft) {
+            // diagonal step moves in both axes
+            dx.max(dy)
+        } else {
+            dx + dy
+        }
+    };
+    let vertical = |dy: usize| match boundary {
+        Boundary::Wrap | Boundary::Klein => dy.min(d.height - dy),
+        Boundary::Solid | Boundary::Mobius => dy,
+    };
+
+    let dx = a.x.abs_diff(b.x);
+    let direct = axes(dx, vertical(a.y.abs_diff(b.y)));
+    let over_side_edge = match boundary {
+        Boundary::Solid => return direct,
+        Boundary::Wrap => axes(d.width - dx, vertical(a.y.abs_diff(b.y))),
+        // side edges are joined upside down
+        Boundary::Mobius | Boundary::Klein => {
+            axes(d.width - dx, vertical(a.y.abs_diff(d.height - 1 - b.y)))
+        }
+    };
+    direct.min(over_side_edge)
+}
+
+/// Random movement which never steers into snake.
+pub struct RandomSafe {
+    rng: StdRng,
+}
+
+impl RandomSafe {
+    pub fn new() -> Self {
+        Self {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomSafe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: SnakeBehavior, F: FruitBehavior> Controller<S, F> for RandomSafe {
+    fn next_direction(&mut self, game: &Game<S, F>) -> MovementDirection {
+        safe_directions(game.level(), game.snake())
+            .choose(&mut self.rng)
+            .cloned()
+            .unwrap_or_else(|| game.snake().direction())
+    }
+}
+
+/// Safe movement which brings snake closer to nearest fruit.
+#[derive(Default)]
+pub struct GreedyFruit;
+
+impl<S: SnakeBehavior, F: FruitBehavior> Controller<S, F> for GreedyFruit {
+    fn next_direction(&mut self, game: &Game<S, F>) -> MovementDirection {
+        let level = game.level();
+        let snake = game.snake();
+        let boundary = snake.boundary();
+        let safe = safe_directions(level, snake);
+
+        let head = match head_position(level, snake) {
+            Some(head) => head,
+            None => return snake.direction(),
+        };
+        let nearest_fruit = level
+            .tiles()
+            .filter(|t| matches!(t.tile_type(), TileType::Fruit))
+            .map(|t| level.get_tile_position(&t))
+            .min_by_key(|fruit| distance(level, boundary, &head, fruit));
+
+        match nearest_fruit {
+            Some(fruit) => safe.into_iter().min_by_key(|d| {
+                step(level, boundary, &head, *d)
+                    .map_or(usize::MAX, |next| distance(level, boundary, &next, &fruit))
+            }),
+            None => safe.first().cloned(),
+        }
+        .unwrap_or_else(|| snake.direction())
+    }
+}
+
+/// Follows shortest path (BFS) to nearest reachable fruit.
+#[derive(Default)]
+pub struct ShortestPath;
+
+impl ShortestPath {
+    /// First direction of shortest path from head to fruit.
+    fn path_to_fruit(level: &GameLevel, snake: &dyn SnakeBehavior) -> Option<MovementDirection> {
+        let dimensions = level.level_dimensions();
+        let head = head_position(level, snake)?;
+        let current = snake.direction();
+        let boundary = snake.boundary();
+
+        // first direction used to reach tile
+        let mut visited = vec![None; dimensions.width * dimensions.height];
+        let mut queue = VecDeque::new();
+
+        let directions = level.topology().directions();
+        for direction in directions
+            .iter()
+            .copied()
+            .filter(|d| !d.is_opposite_to(&current))
+        {
+            let next = match step(level, boundary, &head, direction) {
+                Some(next) => next,
+                None => continue,
+            };
+            let index = next.y * dimensions.width + next.x;
+            if is_safe(level, Some(&next)) && visited[index].is_none() {
+                visited[index] = Some(direction);
+                queue.push_back(next);
+            }
+        }
+
+        while let Some(position) = queue.pop_front() {
+            let index = position.y * dimensions.width + position.x;
+            let first_direction = visited[index];
+            if let Some(TileType::Fruit) = tile_type_on(level, &position) {
+                return first_direction;
+            }
+
+            for direction in directions {
+                let next = match step(level, boundary, &position, *direction) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                let next_index = next.y * dimensions.width + next.x;
+                if is_safe(level, Some(&next)) && visited[next_index].is_none() {
+                    visited[next_index] = first_direction;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<S: SnakeBehavior, F: FruitBehavior> Controller<S, F> for ShortestPath {
+    fn next_direction(&mut self, game: &Game<S, F>) -> MovementDirection {
+        Self::path_to_fruit(game.level(), game.snake())
+            .unwrap_or_else(|| GreedyFruit.next_direction(game))
+    }
+}
+
+/// Follows hamiltonian cycle covering whole level.
+///
+/// Snake whose whole body lies on cycle in cycle order never collides, it fills whole level and
+/// game ends with `GameOutcome::BoardFull`. Until then (snake put on level off the cycle, or
+/// steered away from it by votes) next cycle step may be unsafe, and snake is steered with
+/// `ShortestPath`, which does not guarantee survival. No shortcuts are taken: skipped tiles stay
+/// empty until tail passes them, and snake eating fruits on all tiles ahead before that would
+/// run into its tail with level not full.
+/// Requires square grid level with even width or height, otherwise behaves like `ShortestPath`.
+#[derive(Default)]
+pub struct HamiltonianCycle {
+    /// direction to next tile of cycle, by tile index
+    cycle: Vec<MovementDirection>,
+}
+
+impl HamiltonianCycle {
+    fn build_cycle(width: usize, height: usize) -> Vec<MovementDirection> {
+        use MovementDirection::*;
+
+        let mut cycle = vec![Right; width * height];
+        let transposed = !height.is_multiple_of(2);
+        // build cycle for even number of rows, for odd rows build on transposed level
+        let (rows, columns) = if transposed {
+            (width, height)
+        } else {
+            (height, width)
+        };
+
+        for row in 0..rows {
+            for column in 0..columns {
+                // column 0 is way back to first row
+                let direction = if column == 0 {
+                    if row == 0 {
+                        Right
+                    } else {
+                        Up
+                    }
+                } else if row % 2 == 0 {
+                    if column == columns - 1 {
+                        Down
+                    } else {
+                        Right
+                    }
+                } else if column == 1 {
+                    if row == rows - 1 {
+                        Left
+                    } else {
+                        Down
+                    }
+                } else {
+                    Left
+                };
+
+                let (x, y, direction) = if transposed {
+                    let swapped = match direction {
+                        Up => Left,
+                        Down => Right,
+                        Left => Up,
+                        Right => Down,
+                        // cycle is built only from square grid directions
+                        diagonal => diagonal,
+                    };
+                    (row, column, swapped)
+                } else {
+                    (column, row, direction)
+                };
+                cycle[y * width + x] = direction;
+            }
+        }
+
+        cycle
+    }
+}
+
+impl<S: SnakeBehavior, F: FruitBehavior> Controller<S, F> for HamiltonianCycle {
+    fn next_direction(&mut self, game: &Game<S, F>) -> MovementDirection {
+        let level = game.level();
+        let snake = game.snake();
+        let d = level.level_dimensions();
+
+        if !d.width.is_multiple_of(2) && !d.height.is_multiple_of(2)
+            || d.width < 2
+            || d.height < 2
+            || !level.topology().supports(MovementDirection::Up)
+        {
+            return ShortestPath.next_direction(game);
+        }
+        if self.cycle.len() != d.width * d.height {
+            self.cycle = Self::build_cycle(d.width, d.height);
+        }
+
+        let head = match snake.tiles().first() {
+            Some(head) => *head,
+            None => return snake.direction(),
+        };
+        let direction = self.cycle[head];
+
+        // snake is not on cycle yet, or cycle step leads into its body
+        let head_position = match level.get_tile(head) {
+            Some(tile) => level.get_tile_position(&tile),
+            None => return snake.direction(),
+        };
+        if direction.is_opposite_to(&snake.direction())
+            || !is_safe(
+                level,
+                step(level, snake.boundary(), &head_position, direction).as_ref(),
+            )
+        {
+            return ShortestPath.next_direction(game);
+        }
+
+        direction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Controller, GreedyFruit, HamiltonianCycle, RandomSafe, ShortestPath};
+    use crate::snake_game::{
+        snake::Boundary, snake::SnakeUnbounded, testing::NullFruit, Game, GameLevel,
+        MovementDirection, SnakeBehavior,
+    };
+
+    #[test]
+    fn hamiltonian_cycle_visits_all_tiles() {
+        for (width, height) in [(4, 4), (5, 4), (4, 5)] {
+            let cycle = HamiltonianCycle::build_cycle(width, height);
+            let level = GameLevel::new(width, height);
+
+            let mut visited = vec![false; width * height];
+            let mut position = level.get_tile_position(&level.get_tile(0).unwrap());
+            for _ in 0..width * height {
+                let index = position.y * width + position.x;
+                assert!(!visited[index]);
+                visited[index] = true;
+                position = super::step(&level, Boundary::Solid, &position, cycle[index]).unwrap();
+            }
+
+            assert!(visited.into_iter().all(|v| v));
+            assert_eq!((position.x, position.y), (0, 0));
+        }
+    }
+
+    #[test]
+    fn hamiltonian_cycle_fills_level() {
+        use crate::snake_game::{GameBuilder, GameOutcome};
+
+        for (width, height, boundary) in [
+            (6, 4, Boundary::Solid),
+            (5, 4, Boundary::Wrap),
+            (20, 10, Boundary::Mobius),
+        ] {
+            for seed in 0..10 {
+                let mut game = GameBuilder::new(width, height)
+                    .boundary(boundary)
+                    .direction(MovementDirection::Right)
+                    .random_fruits(5, 1.0)
+                    .seed(seed)
+                    .build()
+                    .unwrap();
+                let mut controller = HamiltonianCycle::default();
+
+                let outcome = loop {
+                    let direction = controller.next_direction(&game);
+                    game.set_snake_direction(direction).unwrap();
+                    match game.try_move() {
+                        Ok(GameOutcome::Running) => (),
+                        outcome => break outcome,
+                    }
+                };
+                assert!(matches!(outcome, Ok(GameOutcome::BoardFull)));
+                assert_eq!(game.snake().len(), width * height);
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_path_wraps_level_edges() {
+        let mut level = GameLevel::new(20, 10);
+        let mut snake = SnakeUnbounded::new(MovementDirection::Up);
+        level.put_fruit(10, 8);
+        snake.put_on(&mut level, 2).unwrap();
+
+        let mut game = Game::new(level, snake, NullFruit);
+        // move snake head to (10, 1)
+        for _ in 0..3 {
+            game.try_move().unwrap();
+        }
+        game.set_snake_direction(MovementDirection::Right).unwrap();
+        game.try_move().unwrap();
+
+        // fruit is closer through top edge of level
+        assert_eq!(ShortestPath.next_direction(&game), MovementDirection::Up);
+    }
+
+    #[test]
+    fn controllers_respect_solid_boundary() {
+        use crate::snake_game::GameBuilder;
+
+        let game = GameBuilder::new(10, 5)
+            .boundary(Boundary::Solid)
+            .snake(MovementDirection::Right, &[(9, 2), (8, 2), (7, 2)])
+            .fruit(0, 2)
+            .fruit_policy(NullFruit)
+            .build()
+            .unwrap();
+
+        // fruit is not reachable over right edge
+        for direction in [
+            ShortestPath.next_direction(&game),
+            GreedyFruit.next_direction(&game),
+            RandomSafe::seeded(1).next_direction(&game),
+        ] {
+            assert_ne!(direction, MovementDirection::Right);
+        }
+    }
+
+    #[test]
+    fn random_safe_does_not_reverse() {
+        let mut level = GameLevel::new(20, 10);
+        let mut snake = SnakeUnbounded::new(MovementDirection::Right);
+        snake.put_on(&mut level, 2).unwrap();
+        let game = Game::new(level, snake, NullFruit);
+
+        let mut controller = RandomSafe::seeded(1);
+        for _ in 0..16 {
+            let direction = controller.next_direction(&game);
+            assert_ne!(direction, MovementDirection::Left);
+        }
+    }
+}
@@ -1,50 +1,50 @@
 use super::{FruitBehavior, GameError, GameLevel, TileType};
+use rand::{rngs::StdRng, SeedableRng};
 
 pub struct FruitRandomLimited {
     limit: usize,
     chance: f64,
+    rng: StdRng,
 }
 
 impl FruitRandomLimited {
     pub fn new(limit: usize, chance: f64) -> Self {
+        Self::with_rng(limit, chance, StdRng::from_entropy())
+    }
+
+    /// Fruits placement repeatable for given seed.
+    pub fn seeded(limit: usize, chance: f64, seed: u64) -> Self {
+        Self::with_rng(limit, chance, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(limit: usize, chance: f64, rng: StdRng) -> Self {
         if !(0.01..=1.0).contains(&chance) {
             panic!("Invalid configuration: chance must be in range of 0.01 - 1.00")
         }
 
-        Self { limit, chance }
+        Self { limit, chance, rng }
     }
 }
 
 impl FruitBehavior for FruitRandomLimited {
     fn put_on(&mut self, level: &mut GameLevel) -> Result<(), GameError> {
-        use rand::prelude::*;
-
-        // count fruits on level
-        let count_fruits = level
-            .level()
-            .iter()
-            .filter(|t| matches!(t.tile_type(), TileType::Fruit))
-            .count();
+        use rand::Rng;
 
         // if under limit then draw a chance to put one fruit
-        if count_fruits < self.limit {
-            let draw = thread_rng().gen_range(0.01..=1.0);
+        if level.count(TileType::Fruit) < self.limit {
+            let draw = self.rng.gen_range(0.01..=1.0);
             if draw <= self.chance {
-                let empty_tiles = level
-                    .level()
-                    .iter()
-                    .filter(|t| matches!(t.tile_type(), TileType::Empty))
-                    .collect::<Vec<_>>();
+                let empty_tiles = level.count(TileType::Empty);
 
                 // there is nowhere to put fruit
-                if empty_tiles.is_empty() {
+                if empty_tiles == 0 {
                     return Ok(());
                 }
 
                 // put fruit on empty field
-                let random_index = thread_rng().gen_range(0..empty_tiles.len());
-                let tile = level.get_tile_mut(random_index).unwrap();
-                tile.set_to(TileType::Fruit);
+                if let Some(random_index) = level.free_tile(self.rng.gen_range(0..empty_tiles)) {
+                    level.set_tile(random_index, TileType::Fruit);
+                }
             }
         }
 
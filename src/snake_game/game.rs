@@ -1,10 +1,31 @@
 use super::{
-    game_level::GameLevel, FruitBehavior, GameDisplay, GameError, MovementDirection, SnakeBehavior,
+    game_level::GameLevel, scenario::WinCondition, FruitBehavior, GameDisplay, GameError,
+    MovementDirection, SnakeBehavior, TileChange, TileType, TurnError,
 };
+use std::collections::VecDeque;
+
 pub struct Game<S, F> {
     snake: S,
     fruit: F,
     level: GameLevel,
+    /// turns queued for next moves, one is applied on each move
+    turns: VecDeque<MovementDirection>,
+    turns_depth: usize,
+    /// all conditions must be met to win the game, game without conditions never ends with win
+    win_conditions: Vec<WinCondition>,
+    /// pre-placed fruits not eaten yet (tile indexes)
+    goal_fruits: Vec<usize>,
+    moves: u64,
+}
+
+/// Result of successful move.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameOutcome {
+    Running,
+    /// all win conditions are met
+    Won,
+    /// snake fills whole level, there is nowhere to move
+    BoardFull,
 }
 
 impl<S: SnakeBehavior, F: FruitBehavior> Game<S, F> {
@@ -13,9 +34,91 @@ impl<S: SnakeBehavior, F: FruitBehavior> Game<S, F> {
             snake,
             fruit,
             level,
+            turns: VecDeque::new(),
+            turns_depth: 0,
+            win_conditions: Vec::new(),
+            goal_fruits: Vec::new(),
+            moves: 0,
         }
     }
 
+    /// Set conditions to win the game, fruits currently on level are the ones to be eaten.
+    pub fn set_win_conditions(&mut self, conditions: Vec<WinCondition>) {
+        self.goal_fruits = self
+            .level
+            .tiles()
+            .filter(|t| t.tile_type() == TileType::Fruit)
+            .map(|t| t.get_index())
+            .collect();
+        self.win_conditions = conditions;
+    }
+
+    pub fn win_conditions(&self) -> &[WinCondition] {
+        &self.win_conditions
+    }
+
+    /// Number of successful moves.
+    pub fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    fn is_won(&self) -> bool {
+        let head = self.snake.tiles().first().cloned();
+
+        !self.win_conditions.is_empty()
+            && self.win_conditions.iter().all(|condition| match condition {
+                WinCondition::Length(length) => self.snake.len() >= *length,
+                WinCondition::EatAllFruits => self.goal_fruits.is_empty(),
+                WinCondition::SurviveTicks(ticks) => self.moves >= *ticks,
+                WinCondition::ReachTile { x, y } => {
+                    head.is_some() && head == self.level.get_tile_on(*x, *y).map(|t| t.get_index())
+                }
+            })
+    }
+
+    /// Enable buffering of up to `depth` turns (disabled with 0).
+    pub fn set_input_buffer(&mut self, depth: usize) {
+        self.turns_depth = depth;
+        self.turns.truncate(depth);
+    }
+
+    /// Queue turn applied on one of next moves, turn is validated against last queued direction.
+    pub fn queue_direction(&mut self, new_direction: MovementDirection) -> Result<(), TurnError> {
+        let last_direction = self.last_queued_direction();
+        if self.turns.len() >= self.turns_depth {
+            return Err(TurnError::BufferFull);
+        }
+        if !self.level.topology().supports(new_direction) {
+            return Err(TurnError::Unsupported);
+        }
+        if last_direction.is_opposite_to(&new_direction) {
+            return Err(TurnError::Opposite);
+        }
+        if last_direction == new_direction {
+            return Err(TurnError::Unchanged);
+        }
+
+        self.turns.push_back(new_direction);
+        Ok(())
+    }
+
+    /// Direction of snake after applying all queued turns.
+    pub fn last_queued_direction(&self) -> MovementDirection {
+        self.turns
+            .back()
+            .cloned()
+            .unwrap_or_else(|| self.snake.direction())
+    }
+
+    /// Number of turns waiting in input buffer.
+    pub fn queued_turns(&self) -> usize {
+        self.turns.len()
+    }
+
+    pub fn clear_input_buffer(&mut self) {
+        self.turns.clear();
+    }
+
     pub fn put_snake(&mut self, tail_size: usize) -> Result<(), GameError> {
         self.snake.put_on(&mut self.level, tail_size)
     }
@@ -24,9 +127,17 @@ impl<S: SnakeBehavior, F: FruitBehavior> Game<S, F> {
         &mut self,
         new_direction: MovementDirection,
     ) -> Result<(), GameError> {
+        if !self.level.topology().supports(new_direction) {
+            return Err(GameError::UnsupportedDirection(new_direction));
+        }
         self.snake.set_direction(new_direction)
     }
 
+    /// Tiles changed since previous call.
+    pub fn take_changes(&mut self) -> Vec<TileChange> {
+        self.level.take_changes()
+    }
+
     pub fn level(&self) -> &GameLevel {
         &self.level
     }
@@ -42,10 +153,29 @@ impl<S: SnakeBehavior, F: FruitBehavior> Game<S, F> {
         renderer.render(self)
     }
 
-    pub fn try_move(&mut self) -> Result<(), GameError> {
+    pub fn try_move(&mut self) -> Result<GameOutcome, GameError> {
+        if let Some(turn) = self.turns.pop_front() {
+            // direction could be changed directly after queueing turn
+            if !turn.is_opposite_to(&self.snake.direction()) {
+                self.snake.set_direction(turn)?;
+            }
+        }
         self.snake.make_move(&mut self.level)?;
+        self.moves += 1;
+
+        // checked before new fruit is put on eaten fruit tile
+        let level = &self.level;
+        self.goal_fruits
+            .retain(|i| matches!(level.get_tile(*i), Some(t) if t.tile_type() == TileType::Fruit));
+        if self.is_won() {
+            return Ok(GameOutcome::Won);
+        }
+        if self.level.count(TileType::Empty) == 0 && self.level.count(TileType::Fruit) == 0 {
+            return Ok(GameOutcome::BoardFull);
+        }
+
         self.fruit.put_on(&mut self.level)?;
 
-        Ok(())
+        Ok(GameOutcome::Running)
     }
 }
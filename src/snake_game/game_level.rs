@@ -1,10 +1,37 @@
+use super::topology::{Boundary, Square, Topology};
+use super::MovementDirection;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// Max number of level tiles (32768x32768), level takes about 3 bits per tile.
+pub const MAX_TILES: usize = 1 << 30;
+const TILE_BITS: usize = 2;
+const TILES_PER_WORD: usize = u64::BITS as usize / TILE_BITS;
+/// low bit of each tile in word
+const TILE_LOW_BITS: u64 = 0x5555_5555_5555_5555;
+/// words of tiles counted together in free tiles index
+const FREE_BLOCK_WORDS: usize = 8;
+const FREE_BLOCK_TILES: usize = FREE_BLOCK_WORDS * TILES_PER_WORD;
+
+/// Level tiles with type of each tile packed into 2 bits.
 #[derive(Clone)]
 pub struct GameLevel {
     height: usize,
     width: usize,
-    level: Vec<Tile>,
+    tiles: Vec<u64>,
+    /// number of empty tiles in blocks of `FREE_BLOCK_TILES` tiles, as fenwick tree
+    /// (node `i` counts blocks `i - lowbit(i) .. i`, node 0 is unused)
+    free_blocks: Vec<u32>,
+    /// number of tiles by tile type
+    counts: [usize; TileType::COUNT],
+    /// indexes of tiles changed since last `take_changes`
+    dirty: Vec<u32>,
+    /// bit set of tiles in `dirty`
+    is_dirty: Vec<u64>,
+    topology: Arc<dyn Topology>,
 }
 
+/// Level tile, read from level by value.
 #[derive(Copy, Clone, Default)]
 pub struct Tile {
     index: usize,
@@ -21,37 +48,133 @@ impl Tile {
     pub fn tile_type(&self) -> TileType {
         self.r#type
     }
+}
 
-    #[inline]
+/// Level tile borrowed for change, see [`GameLevel::get_tile_mut`].
+pub struct TileMut<'a> {
+    level: &'a mut GameLevel,
+    tile: Tile,
+}
+
+impl std::ops::Deref for TileMut<'_> {
+    type Target = Tile;
+
+    fn deref(&self) -> &Tile {
+        &self.tile
+    }
+}
+
+impl TileMut<'_> {
+    /// Change tile type on level.
     pub fn set_to(&mut self, tile_type: TileType) {
-        self.r#type = tile_type;
+        self.level.set_tile(self.tile.index, tile_type);
+        self.tile.r#type = tile_type;
     }
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TileType {
     #[default]
     Empty,
     Snake,
     Fruit,
+    Wall,
+}
+
+impl TileType {
+    const COUNT: usize = 4;
+
+    fn index(&self) -> usize {
+        match self {
+            Self::Empty => 0,
+            Self::Snake => 1,
+            Self::Fruit => 2,
+            Self::Wall => 3,
+        }
+    }
+
+    fn from_index(index: u64) -> Self {
+        match index {
+            1 => Self::Snake,
+            2 => Self::Fruit,
+            3 => Self::Wall,
+            _ => Self::Empty,
+        }
+    }
+}
+
+/// Reason of rejected level size.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LevelError {
+    /// width or height is 0
+    Empty,
+    /// level has more than `MAX_TILES` tiles
+    TooLarge,
 }
 
 impl GameLevel {
+    /// # Panics
+    /// On level size rejected by [`GameLevel::try_new`].
     pub fn new(width: usize, height: usize) -> Self {
-        let size = height * width;
-        let level = (0..size)
-            .into_iter()
-            .map(|i| Tile {
-                index: i,
-                ..Default::default()
-            })
-            .collect();
+        match Self::try_new(width, height) {
+            Ok(level) => level,
+            Err(err) => panic!("Invalid level size {width}x{height}: {err}"),
+        }
+    }
+
+    /// Level with square grid.
+    pub fn try_new(width: usize, height: usize) -> Result<Self, LevelError> {
+        Self::try_with_topology(width, height, Arc::new(Square))
+    }
+
+    pub fn try_with_topology(
+        width: usize,
+        height: usize,
+        topology: Arc<dyn Topology>,
+    ) -> Result<Self, LevelError> {
+        if width == 0 || height == 0 {
+            return Err(LevelError::Empty);
+        }
+        let size = width
+            .checked_mul(height)
+            .filter(|size| *size <= MAX_TILES)
+            .ok_or(LevelError::TooLarge)?;
+
+        let mut counts = [0; TileType::COUNT];
+        counts[TileType::Empty.index()] = size;
+
+        // all tiles are empty
+        let blocks = size.div_ceil(FREE_BLOCK_TILES);
+        let mut free_blocks = vec![0; blocks + 1];
+        for block in 1..=blocks {
+            let tiles = FREE_BLOCK_TILES.min(size - (block - 1) * FREE_BLOCK_TILES);
+            free_blocks[block] += tiles as u32;
+            let parent = block + (block & block.wrapping_neg());
+            if parent <= blocks {
+                free_blocks[parent] += free_blocks[block];
+            }
+        }
 
-        Self {
+        Ok(Self {
             height,
             width,
-            level,
-        }
+            tiles: vec![0; size.div_ceil(TILES_PER_WORD)],
+            free_blocks,
+            counts,
+            dirty: Vec::new(),
+            is_dirty: vec![0; size.div_ceil(u64::BITS as usize)],
+            topology,
+        })
+    }
+
+    pub fn topology(&self) -> &dyn Topology {
+        self.topology.as_ref()
+    }
+
+    /// Shared handle of level topology.
+    pub fn topology_handle(&self) -> Arc<dyn Topology> {
+        self.topology.clone()
     }
 
     pub fn level_coordinates(&self) -> LevelCoordinates {
@@ -71,8 +194,12 @@ impl GameLevel {
         }
     }
 
-    pub fn level(&self) -> &[Tile] {
-        self.level.as_slice()
+    /// All level tiles, row by row.
+    pub fn tiles(&self) -> impl ExactSizeIterator<Item = Tile> + '_ {
+        (0..self.width * self.height).map(|index| Tile {
+            index,
+            r#type: self.tile_type_at(index),
+        })
     }
 
     pub fn get_tile_position(&self, tile: &Tile) -> TileXY {
@@ -85,89 +212,242 @@ impl GameLevel {
         TileXY { x, y }
     }
 
-    pub fn get_tile(&self, index: usize) -> Option<&Tile> {
-        self.level.get(index)
+    #[inline]
+    fn tile_type_at(&self, index: usize) -> TileType {
+        let word = self.tiles[index / TILES_PER_WORD];
+        let shift = (index % TILES_PER_WORD) * TILE_BITS;
+        TileType::from_index((word >> shift) & 0b11)
     }
 
-    pub fn get_tile_mut(&mut self, index: usize) -> Option<&mut Tile> {
-        self.level.get_mut(index)
+    pub fn get_tile(&self, index: usize) -> Option<Tile> {
+        if index < self.width * self.height {
+            Some(Tile {
+                index,
+                r#type: self.tile_type_at(index),
+            })
+        } else {
+            None
+        }
     }
 
-    pub unsafe fn get_tile_mut_unchecked(&mut self, index: usize) -> &mut Tile {
-        self.level.get_unchecked_mut(index)
+    /// Change type of tile on index, keeps free tiles index up to date.
+    /// Returns `None` when tile is out of level.
+    pub fn set_tile(&mut self, index: usize, tile_type: TileType) -> Option<()> {
+        let previous = self.get_tile(index)?.tile_type();
+        let word = &mut self.tiles[index / TILES_PER_WORD];
+        let shift = (index % TILES_PER_WORD) * TILE_BITS;
+        *word = (*word & !(0b11 << shift)) | ((tile_type.index() as u64) << shift);
+
+        let (dirty_word, dirty_bit) = (index / u64::BITS as usize, index % u64::BITS as usize);
+        if self.is_dirty[dirty_word] & (1 << dirty_bit) == 0 {
+            self.is_dirty[dirty_word] |= 1 << dirty_bit;
+            self.dirty.push(index as u32);
+        }
+
+        self.counts[previous.index()] -= 1;
+        self.counts[tile_type.index()] += 1;
+
+        match (previous, tile_type) {
+            (TileType::Empty, TileType::Empty) => (),
+            (TileType::Empty, _) => self.update_free_block(index, |free| free - 1),
+            (_, TileType::Empty) => self.update_free_block(index, |free| free + 1),
+            _ => (),
+        }
+
+        Some(())
     }
 
-    fn is_tile_in_level_bounds(&self, x: usize, y: usize) -> bool {
-        // hide dimensional logic here
-        let d = self.level_coordinates();
-        x <= d.x_max && y <= d.y_max
+    fn update_free_block(&mut self, index: usize, update: impl Fn(u32) -> u32) {
+        let mut node = index / FREE_BLOCK_TILES + 1;
+        while node < self.free_blocks.len() {
+            self.free_blocks[node] = update(self.free_blocks[node]);
+            node += node & node.wrapping_neg();
+        }
     }
 
-    pub fn get_tile_on(&self, x: usize, y: usize) -> Option<&Tile> {
-        if self.is_tile_in_level_bounds(x, y) {
-            self.level.get(y * self.width + x)
+    /// Bit set on low bit of each empty tile in word of tiles.
+    fn empty_tiles_in_word(&self, word: usize) -> u64 {
+        let tiles = self.tiles[word];
+        let empty = !(tiles | tiles >> 1) & TILE_LOW_BITS;
+        // unused bits of last word are not tiles
+        let size = self.width * self.height;
+        let used = size - word * TILES_PER_WORD;
+        if used < TILES_PER_WORD {
+            empty & ((1 << (used * TILE_BITS)) - 1)
         } else {
-            None
+            empty
         }
     }
 
-    pub fn get_tile_mut_on(&mut self, x: usize, y: usize) -> Option<&mut Tile> {
+    pub fn set_tile_on(&mut self, x: usize, y: usize, tile_type: TileType) -> Option<()> {
         if self.is_tile_in_level_bounds(x, y) {
-            self.level.get_mut(y * self.width + x)
+            self.set_tile(y * self.width + x, tile_type)
         } else {
             None
         }
     }
 
-    pub fn tile_sibling(&self, tile: &Tile, on_position: SiblingPosition) -> Option<&Tile> {
-        use SiblingPosition::*;
-        let TileXY { x, y } = self.get_tile_position(tile);
+    /// Number of tiles of given type.
+    pub fn count(&self, tile_type: TileType) -> usize {
+        self.counts[tile_type.index()]
+    }
 
-        match on_position {
-            Up => {
-                if y > 0 {
-                    self.get_tile_on(x, y - 1)
-                } else {
-                    None
+    /// Tiles changed since previous call, with their current type.
+    pub fn take_changes(&mut self) -> Vec<TileChange> {
+        let dirty = std::mem::take(&mut self.dirty);
+        dirty
+            .into_iter()
+            .map(|index| {
+                let index = index as usize;
+                self.is_dirty[index / u64::BITS as usize] &= !(1 << (index % u64::BITS as usize));
+                let tile = Tile {
+                    index,
+                    r#type: self.tile_type_at(index),
+                };
+                let TileXY { x, y } = self.get_tile_position(&tile);
+                TileChange {
+                    x,
+                    y,
+                    tile: tile.tile_type(),
                 }
+            })
+            .collect()
+    }
+
+    /// Index of `n`-th empty tile (in arbitrary order), see [`GameLevel::count`].
+    pub fn free_tile(&self, n: usize) -> Option<usize> {
+        if n >= self.count(TileType::Empty) {
+            return None;
+        }
+
+        // find block with n-th empty tile by descending fenwick tree
+        let blocks = self.free_blocks.len() - 1;
+        let mut block = 0;
+        let mut remaining = n;
+        let mut step = 1 << blocks.ilog2();
+        while step > 0 {
+            let node = block + step;
+            if node <= blocks && (self.free_blocks[node] as usize) <= remaining {
+                block = node;
+                remaining -= self.free_blocks[node] as usize;
             }
-            Down => self.get_tile_on(x, y + 1),
-            Left => {
-                if x > 0 {
-                    self.get_tile_on(x - 1, y)
-                } else {
-                    None
-                }
+            step >>= 1;
+        }
+
+        let words =
+            block * FREE_BLOCK_WORDS..((block + 1) * FREE_BLOCK_WORDS).min(self.tiles.len());
+        for word in words {
+            let mut empty = self.empty_tiles_in_word(word);
+            let count = empty.count_ones() as usize;
+            if remaining >= count {
+                remaining -= count;
+                continue;
+            }
+            for _ in 0..remaining {
+                // clear lowest empty tile
+                empty &= empty - 1;
             }
-            Right => self.get_tile_on(x + 1, y),
+            let tile = empty.trailing_zeros() as usize / TILE_BITS;
+            return Some(word * TILES_PER_WORD + tile);
         }
+
+        None
     }
 
-    // put fruit on arbitrary position (intended for tests)
-    // may fail silently
-    #[cfg(test)]
+    fn is_tile_in_level_bounds(&self, x: usize, y: usize) -> bool {
+        // hide dimensional logic here
+        let d = self.level_coordinates();
+        x <= d.x_max && y <= d.y_max
+    }
+
+    pub fn get_tile_on(&self, x: usize, y: usize) -> Option<Tile> {
+        if self.is_tile_in_level_bounds(x, y) {
+            self.get_tile(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    /// All level tiles, row by row.
+    #[deprecated(note = "tiles are bit-packed, iterate them with `tiles`")]
+    pub fn level(&self) -> Vec<Tile> {
+        self.tiles().collect()
+    }
+
+    #[deprecated(note = "use `set_tile`")]
+    pub fn get_tile_mut(&mut self, index: usize) -> Option<TileMut<'_>> {
+        let tile = self.get_tile(index)?;
+        Some(TileMut { level: self, tile })
+    }
+
+    #[deprecated(note = "use `set_tile_on`")]
+    pub fn get_tile_mut_on(&mut self, x: usize, y: usize) -> Option<TileMut<'_>> {
+        let tile = self.get_tile_on(x, y)?;
+        Some(TileMut { level: self, tile })
+    }
+
+    /// Put wall on arbitrary position, tile which is not empty is left unchanged.
+    pub fn put_wall(&mut self, x: usize, y: usize) {
+        if let Some(TileType::Empty) = self.get_tile_on(x, y).map(|t| t.tile_type()) {
+            self.set_tile_on(x, y, TileType::Wall);
+        }
+    }
+
+    /// Tile next to given one in direction with movement direction after the step
+    /// (changed by non-orientable level edges). `None` when direction is not supported
+    /// by level topology or step hits solid level edge.
+    pub fn neighbour(
+        &self,
+        tile: &Tile,
+        direction: MovementDirection,
+        boundary: Boundary,
+    ) -> Option<(Tile, MovementDirection)> {
+        let TileXY { x, y } = self.get_tile_position(tile);
+        let (dx, dy) = self.topology.offset(x, y, direction)?;
+        let (x, y, direction) = boundary.wrap(
+            self.width,
+            self.height,
+            x as isize + dx,
+            y as isize + dy,
+            direction,
+        )?;
+        Some((self.get_tile_on(x, y)?, direction))
+    }
+
+    /// Put fruit on arbitrary position (intended for tests and scenarios),
+    /// tile which is not empty is left unchanged.
     pub fn put_fruit(&mut self, x: usize, y: usize) {
-        if let Some(tile) = self.get_tile_mut_on(x, y) {
-            if matches!(tile.tile_type(), TileType::Empty) {
-                tile.set_to(TileType::Fruit);
-            }
+        if let Some(TileType::Empty) = self.get_tile_on(x, y).map(|t| t.tile_type()) {
+            self.set_tile_on(x, y, TileType::Fruit);
         }
     }
 }
 
-#[derive(Copy, Clone)]
-pub enum SiblingPosition {
-    Up,
-    Down,
-    Left,
-    Right,
+#[derive(Clone, serde::Serialize)]
+pub struct TileXY {
+    pub x: usize,
+    pub y: usize,
 }
 
-pub struct TileXY {
+/// Tile changed during game move.
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize)]
+pub struct TileChange {
     pub x: usize,
     pub y: usize,
+    pub tile: TileType,
 }
 
+impl Display for LevelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "level has no tiles"),
+            Self::TooLarge => write!(f, "level has more than {MAX_TILES} tiles"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
 pub struct LevelDimensions {
     pub width: usize,
     pub height: usize,
@@ -186,10 +466,129 @@ mod tests {
 
     #[test]
     fn level_tile_coordinates() {
-        let mut level = GameLevel::new(20, 10);
-        let center_by_pos = level.get_tile_mut_on(10, 5).unwrap().get_index();
+        let level = GameLevel::new(20, 10);
+        let center_by_pos = level.get_tile_on(10, 5).unwrap().get_index();
         let center_by_index = level.get_tile(110).unwrap().get_index();
 
         assert_eq!(center_by_index, center_by_pos)
     }
+
+    #[test]
+    fn free_tiles_follow_tile_changes() {
+        use super::TileType;
+
+        let mut level = GameLevel::new(3, 2);
+        level.set_tile(0, TileType::Snake).unwrap();
+        level.set_tile(4, TileType::Fruit).unwrap();
+        level.set_tile(2, TileType::Wall).unwrap();
+        level.set_tile(0, TileType::Empty).unwrap();
+        level.set_tile(4, TileType::Snake).unwrap();
+        assert!(level.set_tile(6, TileType::Fruit).is_none());
+
+        let mut free = (0..level.count(TileType::Empty))
+            .filter_map(|n| level.free_tile(n))
+            .collect::<Vec<_>>();
+        free.sort_unstable();
+        assert_eq!(free, vec![0, 1, 3, 5]);
+        assert_eq!(level.count(TileType::Empty), 4);
+        assert_eq!(level.count(TileType::Snake), 1);
+        assert_eq!(level.count(TileType::Fruit), 0);
+        assert_eq!(level.count(TileType::Wall), 1);
+        for index in free {
+            assert_eq!(level.get_tile(index).unwrap().tile_type(), TileType::Empty);
+        }
+    }
+
+    #[test]
+    fn free_tiles_are_found_across_blocks() {
+        use super::TileType;
+
+        // several free tiles index blocks with partially used last word
+        let mut level = GameLevel::new(301, 3);
+        for index in (0..903).filter(|i| i % 7 == 0 || (250..600).contains(i)) {
+            level.set_tile(index, TileType::Wall).unwrap();
+        }
+        level.set_tile(300, TileType::Empty).unwrap();
+
+        let expected = level
+            .tiles()
+            .filter(|t| t.tile_type() == TileType::Empty)
+            .map(|t| t.get_index())
+            .collect::<Vec<_>>();
+        let free = (0..level.count(TileType::Empty))
+            .map(|n| level.free_tile(n).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(free, expected);
+        assert!(level.free_tile(expected.len()).is_none());
+    }
+
+    #[test]
+    fn changed_tiles_are_reported_once() {
+        use super::{TileChange, TileType};
+
+        let mut level = GameLevel::new(3, 2);
+        level.set_tile(4, TileType::Snake).unwrap();
+        level.set_tile(1, TileType::Snake).unwrap();
+        level.set_tile(4, TileType::Fruit).unwrap();
+
+        let changes = level.take_changes();
+        assert_eq!(
+            changes,
+            vec![
+                TileChange {
+                    x: 1,
+                    y: 1,
+                    tile: TileType::Fruit
+                },
+                TileChange {
+                    x: 1,
+                    y: 0,
+                    tile: TileType::Snake
+                },
+            ]
+        );
+        assert!(level.take_changes().is_empty());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_tile_access_changes_level() {
+        use super::TileType;
+
+        let mut level = GameLevel::new(4, 3);
+        level.get_tile_mut_on(1, 2).unwrap().set_to(TileType::Wall);
+        let mut fruit = level.get_tile_mut(3).unwrap();
+        fruit.set_to(TileType::Fruit);
+        assert_eq!(fruit.get_index(), 3);
+
+        assert_eq!(level.count(TileType::Wall), 1);
+        assert_eq!(level.level()[9].tile_type(), TileType::Wall);
+        assert_eq!(level.get_tile(3).unwrap().tile_type(), TileType::Fruit);
+        assert!(level.get_tile_mut_on(4, 0).is_none());
+    }
+
+    #[test]
+    fn level_size_is_validated() {
+        use super::{LevelError, TileType};
+
+        assert_eq!(GameLevel::try_new(0, 5).err(), Some(LevelError::Empty));
+        assert_eq!(
+            GameLevel::try_new(usize::MAX, 2).err(),
+            Some(LevelError::TooLarge)
+        );
+
+        // tiles packed across words
+        let mut level = GameLevel::try_new(1000, 1000).unwrap();
+        level.set_tile(31, TileType::Wall).unwrap();
+        level.set_tile(32, TileType::Fruit).unwrap();
+        level.set_tile(999_999, TileType::Snake).unwrap();
+        assert_eq!(level.get_tile(31).unwrap().tile_type(), TileType::Wall);
+        assert_eq!(level.get_tile(32).unwrap().tile_type(), TileType::Fruit);
+        assert_eq!(level.get_tile(30).unwrap().tile_type(), TileType::Empty);
+        assert_eq!(
+            level.get_tile(999_999).unwrap().tile_type(),
+            TileType::Snake
+        );
+        assert!(level.get_tile(1_000_000).is_none());
+    }
 }
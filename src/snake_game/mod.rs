@@ -1,20 +1,76 @@
+mod builder;
+pub mod controller;
 pub mod fruit;
 mod game;
 mod game_level;
+mod helper;
 pub mod renderer;
+pub mod scenario;
 pub mod snake;
+pub mod testing;
 #[cfg(test)]
 mod tests;
+pub mod topology;
+pub mod votes;
 
-pub use game::Game;
-pub use game_level::{GameLevel, Tile, TileType};
+pub use builder::{BuildError, GameBuilder};
+pub use game::{Game, GameOutcome};
+pub use game_level::{
+    GameLevel, LevelError, Tile, TileChange, TileMut, TileType, TileXY, MAX_TILES,
+};
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum GameError {
-    GameOver,
-    InvalidInternalState,
-    RenderingError,
+    GameOver(DeathCause),
+    /// broken game invariant
+    InvalidInternalState(&'static str),
+    /// game could not be built from given setup
+    InvalidSetup(BuildError),
+    /// direction is not supported by level topology
+    UnsupportedDirection(MovementDirection),
+    RenderingError(std::fmt::Error),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize)]
+#[serde(tag = "cause", rename_all = "snake_case")]
+pub enum DeathCause {
+    /// snake head moved onto its tail on given position
+    SelfCollision { x: usize, y: usize },
+    /// snake was turned to direction opposite to its movement
+    ReversedIntoItself,
+    /// snake head moved onto wall on given position
+    WallCollision { x: usize, y: usize },
+    /// snake head on given position moved over solid level edge
+    BoundaryCollision { x: usize, y: usize },
+    /// snake head moved onto poisoned tile on given position, built-in levels have
+    /// no poison, cause is reported by custom [`SnakeBehavior`] implementations
+    Poisoned { x: usize, y: usize },
+}
+
+impl DeathCause {
+    /// short name of cause, suited for labels and keys
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SelfCollision { .. } => "self_collision",
+            Self::ReversedIntoItself => "reversed_into_itself",
+            Self::WallCollision { .. } => "wall_collision",
+            Self::BoundaryCollision { .. } => "boundary_collision",
+            Self::Poisoned { .. } => "poisoned",
+        }
+    }
+}
+
+/// Reason of turn rejected by game input buffer.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TurnError {
+    BufferFull,
+    /// turn is opposite to last queued direction
+    Opposite,
+    /// turn is same as last queued direction
+    Unchanged,
+    /// direction is not supported by level topology
+    Unsupported,
 }
 
 pub trait SnakeBehavior {
@@ -24,26 +80,101 @@ pub trait SnakeBehavior {
     fn set_direction(&mut self, new_direction: MovementDirection) -> Result<(), GameError>;
     /// total snake length (with head)
     fn len(&self) -> usize;
+    /// snake is not put on level yet
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// indexes of tiles occupied by snake (head first)
+    fn tiles(&self) -> Vec<usize>;
+    /// policy of level edges snake moves over, used by controllers to predict moves
+    fn boundary(&self) -> topology::Boundary {
+        topology::Boundary::Wrap
+    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MovementDirection {
     Up,
     Down,
     Left,
     Right,
+    /// diagonal directions are supported only by some level topologies, see [`topology::Topology`]
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl MovementDirection {
+    pub const ALL: [Self; 8] = [
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::UpLeft,
+        Self::UpRight,
+        Self::DownLeft,
+        Self::DownRight,
+    ];
+
+    /// short name of direction, same as serialized value
+    pub fn name(&self) -> &'static str {
+        use MovementDirection::*;
+        match self {
+            Up => "up",
+            Down => "down",
+            Left => "left",
+            Right => "right",
+            UpLeft => "up_left",
+            UpRight => "up_right",
+            DownLeft => "down_left",
+            DownRight => "down_right",
+        }
+    }
+
+    pub fn opposite(&self) -> Self {
+        use MovementDirection::*;
+        match self {
+            Up => Down,
+            Down => Up,
+            Left => Right,
+            Right => Left,
+            UpLeft => DownRight,
+            UpRight => DownLeft,
+            DownLeft => UpRight,
+            DownRight => UpLeft,
+        }
+    }
+
     pub fn is_opposite_to(&self, new_direction: &Self) -> bool {
+        self.opposite() == *new_direction
+    }
+
+    /// Direction mirrored upside down.
+    pub fn flipped(&self) -> Self {
         use MovementDirection::*;
-        matches!(
-            (self, new_direction),
-            (Left, Right) | (Right, Left) | (Up, Down) | (Down, Up)
-        )
+        match self {
+            Up => Down,
+            Down => Up,
+            UpLeft => DownLeft,
+            UpRight => DownRight,
+            DownLeft => UpLeft,
+            DownRight => UpRight,
+            Left | Right => *self,
+        }
     }
 }
 
+/// Turn relative to snake movement direction, see [`topology::Topology::turn`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Turn {
+    Left,
+    Right,
+    Straight,
+}
+
 pub trait FruitBehavior {
     fn put_on(&mut self, level: &mut GameLevel) -> Result<(), GameError>;
 }
@@ -57,42 +188,51 @@ pub trait GameDisplay<S: SnakeBehavior, F: FruitBehavior> {
 
 impl Display for GameError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            Self::GameOver => "Game Over",
-            Self::InvalidInternalState => "Game Error: Invalid game state",
-            Self::RenderingError => "Game Error: Rendering Failed",
-        };
-        write!(f, "{s}")
+        match self {
+            Self::GameOver(cause) => write!(f, "Game Over: {cause}"),
+            Self::InvalidInternalState(description) => {
+                write!(f, "Game Error: Invalid game state - {description}")
+            }
+            Self::InvalidSetup(err) => write!(f, "Game Error: Invalid game setup - {err}"),
+            Self::UnsupportedDirection(direction) => write!(
+                f,
+                "Game Error: direction {direction:?} is not supported by level topology"
+            ),
+            Self::RenderingError(_) => write!(f, "Game Error: Rendering Failed"),
+        }
     }
 }
 
-// testing impls
-struct NullSnake;
-impl SnakeBehavior for NullSnake {
-    fn put_on(&mut self, _level: &mut GameLevel, _tail_size: usize) -> Result<(), GameError> {
-        Ok(())
-    }
-
-    fn make_move(&mut self, _level: &mut GameLevel) -> Result<(), GameError> {
-        Ok(())
-    }
-
-    fn direction(&self) -> MovementDirection {
-        MovementDirection::Right
+impl std::error::Error for GameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidSetup(err) => Some(err),
+            Self::RenderingError(err) => Some(err),
+            _ => None,
+        }
     }
+}
 
-    fn set_direction(&mut self, _new_direction: MovementDirection) -> Result<(), GameError> {
-        Ok(())
+impl From<std::fmt::Error> for GameError {
+    fn from(err: std::fmt::Error) -> Self {
+        Self::RenderingError(err)
     }
+}
 
-    fn len(&self) -> usize {
-        0
+impl From<BuildError> for GameError {
+    fn from(err: BuildError) -> Self {
+        Self::InvalidSetup(err)
     }
 }
 
-struct NullFruit;
-impl FruitBehavior for NullFruit {
-    fn put_on(&mut self, _level: &mut GameLevel) -> Result<(), GameError> {
-        Ok(())
+impl Display for DeathCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SelfCollision { x, y } => write!(f, "self collision at ({x}, {y})"),
+            Self::ReversedIntoItself => write!(f, "reversed into itself"),
+            Self::WallCollision { x, y } => write!(f, "wall collision at ({x}, {y})"),
+            Self::BoundaryCollision { x, y } => write!(f, "level edge collision at ({x}, {y})"),
+            Self::Poisoned { x, y } => write!(f, "poisoned at ({x}, {y})"),
+        }
     }
 }
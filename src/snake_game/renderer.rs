@@ -1,4 +1,9 @@
-use super::{FruitBehavior, Game, GameDisplay, GameError, SnakeBehavior, TileType};
+use super::{
+    scenario::WinCondition, topology::Topology, FruitBehavior, Game, GameDisplay, GameError,
+    MovementDirection, SnakeBehavior, TileChange, TileType, TileXY,
+};
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
 pub struct GameDisplayToString;
 
@@ -7,40 +12,231 @@ impl<S: SnakeBehavior, F: FruitBehavior> GameDisplay<S, F> for GameDisplayToStri
     type Error = GameError;
 
     fn render(&self, game: &Game<S, F>) -> Result<Self::Output, Self::Error> {
-        use std::fmt::Write;
+        let level = game.level();
+        let tiles = level.tiles().map(|t| t.tile_type());
+
+        Ok(level_to_text(
+            level.topology(),
+            level.level_dimensions().width,
+            tiles,
+        )?)
+    }
+}
+
+/// Character used for tile in text output.
+pub fn tile_char(tile_type: TileType) -> char {
+    match tile_type {
+        TileType::Empty => ' ',
+        TileType::Fruit => '@',
+        TileType::Snake => '\u{2588}',
+        TileType::Wall => '#',
+    }
+}
+
+/// Level framed with walls, rows are separated with `\n\r`.
+/// Tile on (x, y) is placed in row y + 1 and columns starting at `column + 1` of output,
+/// where `column` is text cell column given by [`Topology::text_cell`].
+fn level_to_text(
+    topology: &dyn Topology,
+    width: usize,
+    tiles: impl ExactSizeIterator<Item = TileType>,
+) -> Result<String, std::fmt::Error> {
+    use std::fmt::Write;
 
+    let columns = topology.text_columns(width);
+    let mut rows = vec![vec![' '; columns]; tiles.len().div_ceil(width)];
+    for (index, tile) in tiles.enumerate() {
+        let (column, row) = topology.text_cell(index % width, index / width);
+        rows[row][column..column + topology.cell_width()].fill(tile_char(tile));
+    }
+
+    let mut output = String::with_capacity((rows.len() + 2) * (columns + 4));
+
+    // main part of horizontal "wall"
+    let v_wall = (0..columns).map(|_| '#').collect::<String>();
+
+    // top wall
+    write!(output, "#{}#", &v_wall)?;
+    for row in rows {
+        write!(output, "\n\r#{}#", row.into_iter().collect::<String>())?;
+    }
+    // bottom wall
+    write!(output, "\n\r#{}#", &v_wall)?;
+
+    Ok(output)
+}
+
+/// Level state as a plain grid of tile types (row by row), suited for image based outputs.
+#[derive(Clone)]
+pub struct TileFrame {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<TileType>,
+    pub topology: Arc<dyn Topology>,
+}
+
+impl TileFrame {
+    /// Same output as [`GameDisplayToString`].
+    pub fn to_text(&self) -> Result<String, std::fmt::Error> {
+        level_to_text(
+            self.topology.as_ref(),
+            self.width,
+            self.tiles.iter().cloned(),
+        )
+    }
+
+    /// Paint tiles changed since this frame.
+    pub fn apply(&mut self, changes: &[TileChange]) {
+        for change in changes {
+            self.tiles[change.y * self.width + change.x] = change.tile;
+        }
+    }
+}
+
+pub struct GameDisplayToTiles;
+
+impl<S: SnakeBehavior, F: FruitBehavior> GameDisplay<S, F> for GameDisplayToTiles {
+    type Output = TileFrame;
+    type Error = GameError;
+
+    fn render(&self, game: &Game<S, F>) -> Result<Self::Output, Self::Error> {
         let level = game.level();
-        let level_box = level.level_coordinates();
         let dimensions = level.level_dimensions();
-        let tiles = level.level();
+        let tiles = level.tiles().map(|t| t.tile_type()).collect();
 
-        let mut output = String::with_capacity(tiles.len());
+        Ok(TileFrame {
+            width: dimensions.width,
+            height: dimensions.height,
+            tiles,
+            topology: level.topology_handle(),
+        })
+    }
+}
 
-        // main part of horizontal "wall"
-        let v_wall = (level_box.x_min..=level_box.x_max)
-            .into_iter()
-            .map(|_| '#')
-            .collect::<String>();
-
-        // top wall
-        write!(output, "#{}#\n\r#", &v_wall).map_err(|_| GameError::RenderingError)?;
-        for (index, tile) in tiles.iter().enumerate() {
-            let char = match tile.tile_type() {
-                TileType::Empty => ' ',
-                TileType::Fruit => '@',
-                TileType::Snake => '\u{2588}',
-            };
-
-            if index > 0 && index % dimensions.width == 0 {
-                // wall at end of line and start of new line
-                write!(output, "#\n\r#").map_err(|_| GameError::RenderingError)?;
+/// Level state with positions of game objects, suited for serialization.
+#[derive(Clone, serde::Serialize)]
+pub struct GameState {
+    pub width: usize,
+    pub height: usize,
+    /// name of level topology, see [`Topology::name`]
+    pub topology: &'static str,
+    pub direction: MovementDirection,
+    pub length: usize,
+    /// snake tiles (head first)
+    pub snake: Vec<TileXY>,
+    pub fruits: Vec<TileXY>,
+    pub walls: Vec<TileXY>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub win_conditions: Vec<WinCondition>,
+}
+
+impl GameState {
+    /// Tiles of level described by state, `None` for unknown topology.
+    pub fn tile_frame(&self) -> Option<TileFrame> {
+        let mut frame = TileFrame {
+            width: self.width,
+            height: self.height,
+            tiles: vec![TileType::Empty; self.width * self.height],
+            topology: super::topology::by_name(self.topology)?,
+        };
+        let tiles = [
+            (&self.walls, TileType::Wall),
+            (&self.fruits, TileType::Fruit),
+            (&self.snake, TileType::Snake),
+        ];
+        for (positions, tile) in tiles {
+            for position in positions {
+                frame.tiles[position.y * self.width + position.x] = tile;
             }
+        }
+        Some(frame)
+    }
+}
+
+pub struct GameDisplayToState;
+
+impl<S: SnakeBehavior, F: FruitBehavior> GameDisplay<S, F> for GameDisplayToState {
+    type Output = GameState;
+    type Error = GameError;
+
+    fn render(&self, game: &Game<S, F>) -> Result<Self::Output, Self::Error> {
+        GameStateTracker::new(game).state(game)
+    }
+}
 
-            write!(output, "{}", char).map_err(|_| GameError::RenderingError)?;
+/// Positions of fruits and walls kept up to date with tile changes, so game state is rendered
+/// on every move without scanning all level tiles.
+pub struct GameStateTracker {
+    /// positions as (y, x), so they are listed row by row
+    fruits: BTreeSet<(usize, usize)>,
+    walls: BTreeSet<(usize, usize)>,
+}
+
+impl GameStateTracker {
+    /// Tracker of current game level, scans all tiles once.
+    pub fn new<S: SnakeBehavior, F: FruitBehavior>(game: &Game<S, F>) -> Self {
+        let level = game.level();
+        let mut tracker = Self {
+            fruits: BTreeSet::new(),
+            walls: BTreeSet::new(),
+        };
+        for tile in level.tiles() {
+            let position = level.get_tile_position(&tile);
+            tracker.set(position.x, position.y, tile.tile_type());
         }
-        // bottom wall
-        write!(output, "#\n\r#{}#", &v_wall).map_err(|_| GameError::RenderingError)?;
+        tracker
+    }
+
+    /// Update positions with tiles changed since previous call, see [`Game::take_changes`].
+    pub fn apply(&mut self, changes: &[TileChange]) {
+        for change in changes {
+            self.set(change.x, change.y, change.tile);
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, tile: TileType) {
+        self.fruits.remove(&(y, x));
+        self.walls.remove(&(y, x));
+        match tile {
+            TileType::Fruit => self.fruits.insert((y, x)),
+            TileType::Wall => self.walls.insert((y, x)),
+            TileType::Empty | TileType::Snake => false,
+        };
+    }
+
+    /// Game state with tracked fruits and walls, tracker must be up to date with game level.
+    pub fn state<S: SnakeBehavior, F: FruitBehavior>(
+        &self,
+        game: &Game<S, F>,
+    ) -> Result<GameState, GameError> {
+        let level = game.level();
+        let snake = game.snake();
+        let dimensions = level.level_dimensions();
+
+        let snake_tiles = snake
+            .tiles()
+            .into_iter()
+            .map(|i| {
+                level
+                    .get_tile(i)
+                    .map(|t| level.get_tile_position(&t))
+                    .ok_or(GameError::InvalidInternalState("snake tile out of level"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let positions = |tiles: &BTreeSet<(usize, usize)>| {
+            tiles.iter().map(|&(y, x)| TileXY { x, y }).collect()
+        };
 
-        Ok(output)
+        Ok(GameState {
+            width: dimensions.width,
+            height: dimensions.height,
+            topology: level.topology().name(),
+            direction: snake.direction(),
+            length: snake.len(),
+            snake: snake_tiles,
+            fruits: positions(&self.fruits),
+            walls: positions(&self.walls),
+            win_conditions: game.win_conditions().to_vec(),
+        })
     }
 }
@@ -0,0 +1,300 @@
+use super::{
+    fruit::FruitRandomLimited, snake::Boundary, BuildError, GameBuilder, MovementDirection,
+};
+
+/// Goal of the game, see [`super::Game::set_win_conditions`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize)]
+#[serde(tag = "goal", rename_all = "snake_case")]
+pub enum WinCondition {
+    /// snake length (with head) is at least given value
+    Length(usize),
+    /// fruits placed on level before game start are eaten
+    EatAllFruits,
+    /// snake made given number of moves
+    SurviveTicks(u64),
+    /// snake head is on given tile
+    ReachTile { x: usize, y: usize },
+}
+
+impl std::fmt::Display for WinCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length(length) => write!(f, "grow to length {length}"),
+            Self::EatAllFruits => write!(f, "eat all fruits"),
+            Self::SurviveTicks(ticks) => write!(f, "survive {ticks} moves"),
+            Self::ReachTile { x, y } => write!(f, "reach tile ({x}, {y})"),
+        }
+    }
+}
+
+/// Game defined by level map with goals.
+///
+/// Map legend: `#` wall, `@` fruit, `H` snake head, `o` snake body, `X` target tile
+/// (adds [`WinCondition::ReachTile`]), `.` empty tile.
+#[derive(Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub map: String,
+    pub direction: MovementDirection,
+    pub conditions: Vec<WinCondition>,
+    pub boundary: Boundary,
+    /// fruits spawned randomly during game (limit, chance), pre-placed fruits only when `None`
+    pub random_fruits: Option<(usize, f64)>,
+}
+
+impl Scenario {
+    /// Game builder with level map applied.
+    pub fn builder(&self) -> Result<GameBuilder<FruitRandomLimited>, BuildError> {
+        let rows = self
+            .map
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| l.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let width = rows.first().map(Vec::len).unwrap_or(0);
+        if rows.iter().any(|r| r.len() != width) {
+            return Err(BuildError::InvalidMap("rows differ in length"));
+        }
+
+        let (limit, chance) = self.random_fruits.unwrap_or((0, 1.0));
+        let mut builder = GameBuilder::new(width, rows.len())
+            .boundary(self.boundary)
+            .random_fruits(limit, chance);
+        let mut conditions = self.conditions.clone();
+        let mut head = None;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                match tile {
+                    '.' | 'o' => (),
+                    '#' => builder = builder.wall(x, y),
+                    '@' => builder = builder.fruit(x, y),
+                    'X' => conditions.push(WinCondition::ReachTile { x, y }),
+                    'H' if head.is_none() => head = Some((x, y)),
+                    'H' => return Err(BuildError::InvalidMap("more than one snake head")),
+                    _ => return Err(BuildError::InvalidMap("unknown tile")),
+                }
+            }
+        }
+
+        let head = head.ok_or(BuildError::InvalidMap("no snake head"))?;
+        let body = trace_snake(&rows, head)?;
+
+        Ok(builder
+            .snake(self.direction, &body)
+            .win_conditions(conditions))
+    }
+}
+
+/// Snake body positions (head first), following `o` tiles from head.
+fn trace_snake(
+    rows: &[Vec<char>],
+    head: (usize, usize),
+) -> Result<Vec<(usize, usize)>, BuildError> {
+    let is_body = |x: usize, y: usize| rows.get(y).and_then(|r| r.get(x)) == Some(&'o');
+
+    let mut body = vec![head];
+    loop {
+        let (x, y) = *body.last().unwrap();
+        let mut next = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .filter(|&(x, y)| is_body(x, y) && !body.contains(&(x, y)));
+
+        match (next.next(), next.next()) {
+            (Some(position), None) => body.push(position),
+            (None, _) => break,
+            _ => return Err(BuildError::InvalidMap("snake body is ambiguous")),
+        }
+    }
+
+    let body_tiles = rows.iter().flatten().filter(|t| **t == 'o').count();
+    if body_tiles != body.len() - 1 {
+        return Err(BuildError::InvalidMap(
+            "snake body is not connected to head",
+        ));
+    }
+
+    Ok(body)
+}
+
+/// Puzzles played in order: solved puzzle moves players to the next one, lost one is played
+/// again, after the last puzzle the sequence starts over.
+pub struct PuzzleSequence {
+    puzzles: Vec<Scenario>,
+    index: usize,
+}
+
+impl PuzzleSequence {
+    pub fn new(puzzles: Vec<Scenario>) -> Self {
+        Self { puzzles, index: 0 }
+    }
+
+    /// Puzzle to play with its number (counting from 1), `None` when sequence is empty.
+    pub fn current(&self) -> Option<(usize, &Scenario)> {
+        self.puzzles.get(self.index).map(|p| (self.index + 1, p))
+    }
+
+    /// Move to the next puzzle.
+    pub fn solved(&mut self) {
+        self.index = (self.index + 1) % self.puzzles.len().max(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.puzzles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.puzzles.is_empty()
+    }
+}
+
+/// Built-in sequence of puzzles, in order of difficulty.
+pub fn puzzles() -> Vec<Scenario> {
+    use MovementDirection::*;
+
+    vec![
+        Scenario {
+            name: "First bite".to_owned(),
+            map: "
+                ....................
+                ....................
+                ..ooH....@....@.....
+                ....................
+                .........@..........
+                ....................
+            "
+            .to_owned(),
+            direction: Right,
+            conditions: vec![WinCondition::EatAllFruits],
+            boundary: Boundary::Wrap,
+            random_fruits: None,
+        },
+        Scenario {
+            name: "Corridor".to_owned(),
+            // open row lets snake run over level edges until players turn it into corridor
+            map: "
+                ####################
+                ..ooH...............
+                #..................#
+                ##############.#####
+                #X.............#####
+                ####################
+            "
+            .to_owned(),
+            direction: Right,
+            conditions: vec![],
+            boundary: Boundary::Wrap,
+            random_fruits: None,
+        },
+        Scenario {
+            name: "Growing up".to_owned(),
+            map: "
+                ....................
+                .@....#......#....@.
+                ......#......#......
+                ...@..#..H...#..@...
+                ......#..o...#......
+                .@....#..o...#....@.
+                ....................
+            "
+            .to_owned(),
+            direction: Up,
+            conditions: vec![WinCondition::Length(7)],
+            // snake runs over top and bottom edges until players steer it to fruits
+            boundary: Boundary::Wrap,
+            random_fruits: None,
+        },
+        Scenario {
+            name: "Endurance".to_owned(),
+            map: "
+                ########....########
+                #..................#
+                #....##......##....#
+                ...........ooH......
+                #....##......##....#
+                #..................#
+                ########....########
+            "
+            .to_owned(),
+            direction: Right,
+            conditions: vec![WinCondition::SurviveTicks(300), WinCondition::Length(10)],
+            boundary: Boundary::Wrap,
+            random_fruits: Some((3, 0.2)),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{puzzles, PuzzleSequence, WinCondition};
+    use crate::snake_game::{GameOutcome, MovementDirection::*};
+
+    #[test]
+    fn puzzles_are_valid() {
+        for puzzle in puzzles() {
+            let builder = puzzle.builder();
+            assert!(builder.is_ok(), "{}: {:?}", puzzle.name, builder.err());
+            assert!(puzzle.builder().unwrap().build().is_ok(), "{}", puzzle.name);
+        }
+    }
+
+    #[test]
+    fn solved_puzzle_moves_to_next_one() {
+        let mut sequence = PuzzleSequence::new(puzzles().into_iter().take(2).collect());
+        assert_eq!(sequence.current().map(|(number, _)| number), Some(1));
+        sequence.solved();
+        assert_eq!(sequence.current().map(|(number, _)| number), Some(2));
+        sequence.solved();
+        assert_eq!(sequence.current().map(|(number, _)| number), Some(1));
+
+        assert!(PuzzleSequence::new(Vec::new()).current().is_none());
+    }
+
+    #[test]
+    fn puzzles_survive_opening_without_input() {
+        // more than ticks after which server AI steers the snake
+        const OPENING_MOVES: u64 = 50;
+
+        for puzzle in puzzles() {
+            let mut game = puzzle.builder().unwrap().seed(1).build().unwrap();
+            for moves in 0..OPENING_MOVES {
+                let outcome = game.try_move();
+                assert!(
+                    outcome.is_ok(),
+                    "{}: move {moves}: {outcome:?}",
+                    puzzle.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn eat_all_fruits_wins() {
+        let puzzle = &puzzles()[0];
+        let mut game = puzzle.builder().unwrap().build().unwrap();
+        assert_eq!(game.win_conditions(), &[WinCondition::EatAllFruits]);
+
+        // head (4, 2), fruits (9, 2), (14, 2), (9, 4)
+        for _ in 0..5 {
+            assert_eq!(game.try_move().unwrap(), GameOutcome::Running);
+        }
+        game.set_snake_direction(Down).unwrap();
+        game.try_move().unwrap();
+        game.try_move().unwrap();
+        game.set_snake_direction(Right).unwrap();
+        game.try_move().unwrap();
+        game.set_snake_direction(Up).unwrap();
+        game.try_move().unwrap();
+        game.try_move().unwrap();
+        game.set_snake_direction(Right).unwrap();
+        for _ in 0..3 {
+            assert_eq!(game.try_move().unwrap(), GameOutcome::Running);
+        }
+        assert_eq!(game.try_move().unwrap(), GameOutcome::Won);
+    }
+}
@@ -1,49 +1,119 @@
 use super::{
-    game_level::{SiblingPosition, Tile},
-    GameError, GameLevel, MovementDirection, SnakeBehavior, TileType,
+    game_level::LevelDimensions, DeathCause, GameError, GameLevel, MovementDirection,
+    SnakeBehavior, TileType, TileXY,
 };
 
 use std::collections::VecDeque;
 
+pub use super::topology::Boundary;
+
 #[derive(Debug)]
-pub struct SnakeUnbounded {
+pub struct Snake {
     tail: VecDeque<usize>,
     movement_direction: MovementDirection,
+    boundary: Boundary,
+    /// directions supported by level topology, empty until snake is put on level
+    directions: &'static [MovementDirection],
 }
 
-enum MovementResult<'a> {
-    Ok(&'a Tile),
-    GrowOn(&'a Tile),
-    SnakeCollision,
-    LevelBoundary,
-}
+/// Snake wrapping around level edges.
+pub type SnakeUnbounded = Snake;
 
-impl SnakeUnbounded {
+impl Snake {
     pub fn new(initial_direction: MovementDirection) -> Self {
+        Self::with_boundary(initial_direction, Boundary::Wrap)
+    }
+
+    /// Snake dying on level edges.
+    pub fn bounded(initial_direction: MovementDirection) -> Self {
+        Self::with_boundary(initial_direction, Boundary::Solid)
+    }
+
+    pub fn with_boundary(initial_direction: MovementDirection, boundary: Boundary) -> Self {
         Self {
             tail: VecDeque::new(),
             movement_direction: initial_direction,
+            boundary,
+            directions: &[],
         }
     }
 
-    fn try_move_to<'l>(&self, to_tile: &Option<&'l Tile>) -> MovementResult<'l> {
-        match to_tile {
-            Some(tile) => match tile.tile_type() {
-                TileType::Empty => MovementResult::Ok(tile),
-                TileType::Fruit => MovementResult::GrowOn(tile),
-                TileType::Snake => MovementResult::SnakeCollision,
+    /// Smallest level fitting snake with given tail size put on level center.
+    pub fn min_level_size(tail_size: usize, direction: MovementDirection) -> LevelDimensions {
+        // tail grows from center tile to the level edge
+        let length = 2 * tail_size + 1;
+        match direction {
+            MovementDirection::Left | MovementDirection::Right => LevelDimensions {
+                width: length,
+                height: 1,
+            },
+            MovementDirection::Up | MovementDirection::Down => LevelDimensions {
+                width: 1,
+                height: length,
+            },
+            _ => LevelDimensions {
+                width: length,
+                height: length,
             },
-            None => MovementResult::LevelBoundary,
         }
     }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// Put snake on given tiles (head first), all tiles must be empty.
+    /// Tiles are expected to form a connected path.
+    pub fn put_on_path(&mut self, level: &mut GameLevel, path: &[usize]) -> Result<(), GameError> {
+        if path.len() < 2 {
+            return Err(GameError::InvalidInternalState(
+                "snake tail size must be at least 1",
+            ));
+        }
+        for tile_index in path {
+            match level.get_tile(*tile_index).map(|t| t.tile_type()) {
+                Some(TileType::Empty) => (),
+                Some(_) => {
+                    return Err(GameError::InvalidInternalState(
+                        "snake placed on occupied tile",
+                    ))
+                }
+                None => return Err(GameError::InvalidInternalState("snake tile out of level")),
+            }
+        }
+
+        // put snake on selected tiles
+        for tile_index in path {
+            level.set_tile(*tile_index, TileType::Snake);
+        }
+
+        // save tail tiles to track movement
+        self.tail = path.iter().cloned().collect();
+        self.directions = level.topology().directions();
+
+        Ok(())
+    }
+
+    /// Index of tile which head moves to with movement direction after the move,
+    /// `None` when it hits solid level edge.
+    fn next_head_tile(
+        &self,
+        level: &GameLevel,
+        head_index: usize,
+    ) -> Option<(usize, MovementDirection)> {
+        let head = level.get_tile(head_index)?;
+        level
+            .neighbour(&head, self.movement_direction, self.boundary)
+            .map(|(tile, direction)| (tile.get_index(), direction))
+    }
 }
 
-impl SnakeBehavior for SnakeUnbounded {
+impl SnakeBehavior for Snake {
     fn put_on(&mut self, level: &mut GameLevel, tail_size: usize) -> Result<(), GameError> {
-        use MovementDirection::*;
-
         if tail_size < 1 {
-            return Err(GameError::InvalidInternalState);
+            return Err(GameError::InvalidInternalState(
+                "snake tail size must be at least 1",
+            ));
         }
 
         let d = level.level_coordinates();
@@ -53,99 +123,94 @@ impl SnakeBehavior for SnakeUnbounded {
         // get head tile
         let head = level
             .get_tile_on(x_center, y_center)
-            .ok_or(GameError::InvalidInternalState)?;
+            .ok_or(GameError::InvalidInternalState("level has no center tile"))?;
 
         // set on which tiles snake lives
         let mut tail = Vec::new();
         tail.push(head.get_index());
 
+        if !level.topology().supports(self.movement_direction) {
+            return Err(GameError::InvalidInternalState(
+                "direction not supported by level topology",
+            ));
+        }
+
         // add tail tiles to direction opposite to movement
-        let grow_direction = match self.movement_direction {
-            Up => SiblingPosition::Down,
-            Down => SiblingPosition::Up,
-            Left => SiblingPosition::Right,
-            Right => SiblingPosition::Left,
+        let grow_direction = self.movement_direction.opposite();
+        let sibling_of = |tile| {
+            level
+                .neighbour(&tile, grow_direction, Boundary::Solid)
+                .map(|(tile, _)| tile)
         };
 
-        let mut sibling = level.tile_sibling(head, grow_direction);
+        let mut sibling = sibling_of(head);
         for _ in 0..tail_size {
             let tail_tile = match sibling {
                 Some(tile) => tile,
-                None => return Err(GameError::InvalidInternalState),
+                None => {
+                    return Err(GameError::InvalidInternalState(
+                        "level is too small for snake tail",
+                    ))
+                }
             };
             tail.push(tail_tile.get_index());
-            sibling = level.tile_sibling(tail_tile, grow_direction);
+            sibling = sibling_of(tail_tile);
         }
 
-        // put snake on selected tiles
-        for tile_index in tail.iter() {
-            let tile = level.get_tile_mut(*tile_index).unwrap();
-            tile.set_to(TileType::Snake);
-        }
-
-        // save tail tiles to track movement
-        self.tail = tail.into_iter().collect::<VecDeque<usize>>();
-
-        Ok(())
+        self.put_on_path(level, &tail)
     }
 
     fn make_move(&mut self, level: &mut GameLevel) -> Result<(), GameError> {
-        use MovementDirection::*;
-
-        let head_index = self.tail.front().ok_or(GameError::InvalidInternalState)?;
+        let head_index = *self
+            .tail
+            .front()
+            .ok_or(GameError::InvalidInternalState("snake has no head"))?;
         let head = level
-            .get_tile(*head_index)
-            .ok_or(GameError::InvalidInternalState)?;
-
-        let to_sibling = match self.movement_direction {
-            Up => SiblingPosition::Up,
-            Down => SiblingPosition::Down,
-            Left => SiblingPosition::Left,
-            Right => SiblingPosition::Right,
+            .get_tile(head_index)
+            .ok_or(GameError::InvalidInternalState("snake head out of level"))?;
+
+        let (next_index, next_direction) = match self.next_head_tile(level, head_index) {
+            Some(next) => next,
+            None => {
+                let TileXY { x, y } = level.get_tile_position(&head);
+                return Err(GameError::GameOver(DeathCause::BoundaryCollision { x, y }));
+            }
         };
-        let to_tile = level.tile_sibling(head, to_sibling);
-
-        let movement_result = self.try_move_to(&to_tile);
+        let next_tile = level
+            .get_tile(next_index)
+            .ok_or(GameError::InvalidInternalState("snake moved out of level"))?;
 
         // normal movement - true, set to false on snake grow.
         let mut delete_tail_end = true;
 
-        let next_tile = match movement_result {
-            // get unchecked is safe - tile exists
-            MovementResult::Ok(tile) => unsafe { level.get_tile_mut_unchecked(tile.get_index()) },
-            MovementResult::GrowOn(tile) => {
-                delete_tail_end = false; // make snake grow by one tile
-                unsafe { level.get_tile_mut_unchecked(tile.get_index()) }
+        match next_tile.tile_type() {
+            TileType::Empty => (),
+            // make snake grow by one tile
+            TileType::Fruit => delete_tail_end = false,
+            TileType::Snake => {
+                let TileXY { x, y } = level.get_tile_position(&next_tile);
+                return Err(GameError::GameOver(DeathCause::SelfCollision { x, y }));
             }
-            MovementResult::SnakeCollision => return Err(GameError::GameOver),
-            // "globe" level behavior logic
-            MovementResult::LevelBoundary => {
-                let head_pos = level.get_tile_position(head);
-                let level_dim = level.level_coordinates();
-
-                match self.movement_direction {
-                    Up => level.get_tile_mut_on(head_pos.x, level_dim.y_max),
-                    Down => level.get_tile_mut_on(head_pos.x, level_dim.y_min),
-                    Left => level.get_tile_mut_on(level_dim.x_max, head_pos.y),
-                    Right => level.get_tile_mut_on(level_dim.x_min, head_pos.y),
-                }
-                .ok_or(GameError::InvalidInternalState)?
+            TileType::Wall => {
+                let TileXY { x, y } = level.get_tile_position(&next_tile);
+                return Err(GameError::GameOver(DeathCause::WallCollision { x, y }));
             }
-        };
+        }
 
-        next_tile.set_to(TileType::Snake);
-        self.tail.push_front(next_tile.get_index());
+        level.set_tile(next_index, TileType::Snake);
+        self.tail.push_front(next_index);
+        // crossing non-orientable level edge turns snake upside down
+        self.movement_direction = next_direction;
 
         // delete last segment
         if delete_tail_end {
             let tail_end_index = self
                 .tail
                 .pop_back()
-                .ok_or(GameError::InvalidInternalState)?;
-            let tail_end = level
-                .get_tile_mut(tail_end_index)
-                .ok_or(GameError::InvalidInternalState)?;
-            tail_end.set_to(TileType::Empty);
+                .ok_or(GameError::InvalidInternalState("snake has no tail end"))?;
+            level.set_tile(tail_end_index, TileType::Empty).ok_or(
+                GameError::InvalidInternalState("snake tail end out of level"),
+            )?;
         }
 
         Ok(())
@@ -157,7 +222,10 @@ impl SnakeBehavior for SnakeUnbounded {
 
     fn set_direction(&mut self, new_direction: MovementDirection) -> Result<(), GameError> {
         if self.movement_direction.is_opposite_to(&new_direction) {
-            return Err(GameError::GameOver);
+            return Err(GameError::GameOver(DeathCause::ReversedIntoItself));
+        }
+        if !self.directions.is_empty() && !self.directions.contains(&new_direction) {
+            return Err(GameError::UnsupportedDirection(new_direction));
         }
         self.movement_direction = new_direction;
         Ok(())
@@ -166,4 +234,12 @@ impl SnakeBehavior for SnakeUnbounded {
     fn len(&self) -> usize {
         self.tail.len()
     }
+
+    fn tiles(&self) -> Vec<usize> {
+        self.tail.iter().cloned().collect()
+    }
+
+    fn boundary(&self) -> Boundary {
+        self.boundary
+    }
 }
@@ -0,0 +1,37 @@
+use super::{FruitBehavior, GameError, GameLevel, MovementDirection, SnakeBehavior};
+
+/// Snake which does nothing, for testing levels and renderers.
+pub struct NullSnake;
+impl SnakeBehavior for NullSnake {
+    fn put_on(&mut self, _level: &mut GameLevel, _tail_size: usize) -> Result<(), GameError> {
+        Ok(())
+    }
+
+    fn make_move(&mut self, _level: &mut GameLevel) -> Result<(), GameError> {
+        Ok(())
+    }
+
+    fn direction(&self) -> MovementDirection {
+        MovementDirection::Right
+    }
+
+    fn set_direction(&mut self, _new_direction: MovementDirection) -> Result<(), GameError> {
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn tiles(&self) -> Vec<usize> {
+        Vec::new()
+    }
+}
+
+/// Fruits policy which never puts fruit on level.
+pub struct NullFruit;
+impl FruitBehavior for NullFruit {
+    fn put_on(&mut self, _level: &mut GameLevel) -> Result<(), GameError> {
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
-use super::{renderer::GameDisplayToString, Game, GameLevel, NullFruit, NullSnake};
+use super::{
+    renderer::GameDisplayToString,
+    testing::{NullFruit, NullSnake},
+    Game, GameLevel,
+};
 
 #[test]
 fn level_render() {
@@ -26,7 +30,7 @@ fn level_render() {
 
 #[test]
 fn snake_movement_and_grow() {
-    use super::{snake::SnakeUnbounded, GameError, MovementDirection, SnakeBehavior};
+    use super::{snake::SnakeUnbounded, DeathCause, GameError, MovementDirection, SnakeBehavior};
 
     let mut level = GameLevel::new(20, 10);
     let mut snake = SnakeUnbounded::new(MovementDirection::Right);
@@ -65,7 +69,7 @@ fn snake_movement_and_grow() {
                 game.set_snake_direction(MovementDirection::Right).unwrap();
                 let result = game.try_move();
                 let mut game_over = false;
-                if let Err(GameError::GameOver) = result {
+                if let Err(GameError::GameOver(DeathCause::SelfCollision { .. })) = result {
                     game_over = true;
                 }
                 assert!(game_over);
@@ -75,3 +79,313 @@ fn snake_movement_and_grow() {
         }
     }
 }
+
+#[test]
+fn snake_reversing_is_game_over() {
+    use super::{snake::SnakeUnbounded, DeathCause, GameError, MovementDirection, SnakeBehavior};
+
+    let mut snake = SnakeUnbounded::new(MovementDirection::Up);
+    let result = snake.set_direction(MovementDirection::Down);
+
+    match result {
+        Err(err @ GameError::GameOver(DeathCause::ReversedIntoItself)) => {
+            assert_eq!(err.to_string(), "Game Over: reversed into itself")
+        }
+        _ => panic!("expected game over"),
+    }
+}
+
+#[test]
+fn snake_rejects_direction_unsupported_by_topology() {
+    use super::{snake::Snake, GameError, GameLevel, MovementDirection::*, SnakeBehavior};
+
+    let mut level = GameLevel::new(8, 8);
+    let mut snake = Snake::new(Right);
+    snake.put_on(&mut level, 2).unwrap();
+
+    assert!(matches!(
+        snake.set_direction(UpLeft),
+        Err(GameError::UnsupportedDirection(UpLeft))
+    ));
+    assert_eq!(snake.direction(), Right);
+    snake.make_move(&mut level).unwrap();
+}
+
+#[test]
+fn buffered_turns_are_applied_one_per_move() {
+    use super::{snake::SnakeUnbounded, MovementDirection::*, SnakeBehavior, TurnError};
+
+    let mut level = GameLevel::new(20, 10);
+    let mut snake = SnakeUnbounded::new(Right);
+    snake.put_on(&mut level, 2).unwrap();
+    let mut game = Game::new(level, snake, NullFruit);
+    game.set_input_buffer(2);
+
+    game.queue_direction(Up).unwrap();
+    assert_eq!(game.queue_direction(Down), Err(TurnError::Opposite));
+    assert_eq!(game.queue_direction(Up), Err(TurnError::Unchanged));
+    game.queue_direction(Left).unwrap();
+    assert_eq!(game.queue_direction(Down), Err(TurnError::BufferFull));
+    assert_eq!(game.last_queued_direction(), Left);
+
+    game.try_move().unwrap();
+    assert_eq!(game.snake().direction(), Up);
+    game.try_move().unwrap();
+    assert_eq!(game.snake().direction(), Left);
+    game.try_move().unwrap();
+    assert_eq!(game.snake().direction(), Left);
+}
+
+#[test]
+fn builder_places_snake_on_exact_path() {
+    use super::{BuildError, GameBuilder, MovementDirection::*, TileType};
+
+    let game = GameBuilder::new(10, 5)
+        .snake(Up, &[(2, 1), (2, 2), (3, 2)])
+        .fruit(5, 1)
+        .wall(7, 3)
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+
+    let level = game.level();
+    let positions = game
+        .snake()
+        .tiles()
+        .into_iter()
+        .map(|i| {
+            let tile = level.get_tile(i).unwrap();
+            let position = level.get_tile_position(&tile);
+            (position.x, position.y)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(positions, vec![(2, 1), (2, 2), (3, 2)]);
+    assert_eq!(
+        level.get_tile_on(5, 1).unwrap().tile_type(),
+        TileType::Fruit
+    );
+    assert_eq!(level.get_tile_on(7, 3).unwrap().tile_type(), TileType::Wall);
+
+    let build = |body: &[(usize, usize)]| {
+        GameBuilder::new(10, 5)
+            .snake(Up, body)
+            .wall(4, 4)
+            .build()
+            .err()
+    };
+    assert_eq!(build(&[(2, 1)]), Some(BuildError::SnakeTooShort));
+    assert_eq!(
+        build(&[(2, 1), (12, 1)]),
+        Some(BuildError::OutOfBounds { x: 12, y: 1 })
+    );
+    assert_eq!(
+        build(&[(2, 1), (2, 3)]),
+        Some(BuildError::Disconnected { x: 2, y: 3 })
+    );
+    assert_eq!(
+        build(&[(4, 3), (4, 4)]),
+        Some(BuildError::Overlap { x: 4, y: 4 })
+    );
+    assert_eq!(build(&[(2, 1), (2, 0)]), Some(BuildError::FacingTail));
+    // segments connected over wrapped level edge
+    assert_eq!(build(&[(0, 1), (9, 1)]), None);
+}
+
+#[test]
+fn snake_dies_on_walls_and_solid_boundary() {
+    use super::{snake::Boundary, DeathCause, GameBuilder, GameError, MovementDirection::*};
+
+    let mut game = GameBuilder::new(10, 5)
+        .snake(Right, &[(2, 1), (1, 1)])
+        .wall(4, 1)
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+    game.try_move().unwrap();
+    match game.try_move() {
+        Err(GameError::GameOver(DeathCause::WallCollision { x: 4, y: 1 })) => (),
+        other => panic!("expected wall collision, got {other:?}"),
+    }
+
+    let mut game = GameBuilder::new(10, 5)
+        .snake(Up, &[(3, 0), (3, 1)])
+        .boundary(Boundary::Solid)
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+    match game.try_move() {
+        Err(GameError::GameOver(DeathCause::BoundaryCollision { x: 3, y: 0 })) => (),
+        other => panic!("expected boundary collision, got {other:?}"),
+    }
+}
+
+#[test]
+fn snake_grows_on_fruit_over_wrapped_edge() {
+    use super::{GameBuilder, MovementDirection::*};
+
+    let mut game = GameBuilder::new(10, 5)
+        .snake(Left, &[(0, 2), (1, 2)])
+        .fruit(9, 2)
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+    game.try_move().unwrap();
+
+    assert_eq!(game.snake().len(), 3);
+}
+
+#[test]
+fn tracked_state_follows_tile_changes() {
+    use super::{
+        renderer::{GameDisplayToState, GameStateTracker},
+        GameBuilder,
+        MovementDirection::*,
+    };
+
+    let mut game = GameBuilder::new(10, 5)
+        .snake(Right, &[(2, 1), (1, 1)])
+        .fruit(3, 1)
+        .fruit(5, 4)
+        .wall(0, 3)
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+    let mut tracker = GameStateTracker::new(&game);
+    game.take_changes();
+    game.try_move().unwrap();
+    tracker.apply(&game.take_changes());
+
+    let tracked = serde_json::to_value(tracker.state(&game).unwrap()).unwrap();
+    let rendered = serde_json::to_value(game.render(&GameDisplayToState).unwrap()).unwrap();
+    assert_eq!(tracked, rendered);
+    assert_eq!(tracked["fruits"], serde_json::json!([{"x": 5, "y": 4}]));
+}
+
+#[test]
+fn state_is_drawn_as_rendered_tiles() {
+    use super::{
+        renderer::{GameDisplayToState, GameDisplayToTiles},
+        GameBuilder,
+        MovementDirection::*,
+    };
+
+    let game = GameBuilder::new(6, 4)
+        .snake(Down, &[(2, 2), (2, 1), (3, 1)])
+        .fruit(5, 0)
+        .walls(&[(0, 3), (1, 3)])
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+    let state = game.render(&GameDisplayToState).unwrap();
+    let frame = state.tile_frame().unwrap();
+
+    assert_eq!(frame.tiles, game.render(&GameDisplayToTiles).unwrap().tiles);
+}
+
+#[test]
+fn game_is_won_when_all_conditions_are_met() {
+    use super::{scenario::WinCondition, GameBuilder, GameOutcome, MovementDirection::*};
+
+    let mut game = GameBuilder::new(10, 5)
+        .snake(Right, &[(2, 1), (1, 1)])
+        .fruit(3, 1)
+        .fruit_policy(NullFruit)
+        .win_conditions(vec![
+            WinCondition::Length(3),
+            WinCondition::SurviveTicks(2),
+            WinCondition::ReachTile { x: 4, y: 1 },
+        ])
+        .build()
+        .unwrap();
+
+    // fruit eaten, length reached
+    assert_eq!(game.try_move().unwrap(), GameOutcome::Running);
+    assert_eq!(game.try_move().unwrap(), GameOutcome::Won);
+    assert_eq!(game.moves(), 2);
+}
+
+#[test]
+fn builder_validates_level_size() {
+    use super::{BuildError, GameBuilder, LevelError, MovementDirection::*};
+
+    assert_eq!(
+        GameBuilder::new(0, 5).build().err(),
+        Some(BuildError::InvalidLevel(LevelError::Empty))
+    );
+    assert_eq!(
+        GameBuilder::new(6, 3).tail_size(3).build().err(),
+        Some(BuildError::LevelTooSmall {
+            min_width: 7,
+            min_height: 1
+        })
+    );
+    assert!(GameBuilder::new(7, 1).tail_size(3).build().is_ok());
+    assert!(GameBuilder::new(1, 7)
+        .direction(Down)
+        .tail_size(3)
+        .build()
+        .is_ok());
+    // explicit snake path does not depend on level center
+    assert!(GameBuilder::new(3, 1)
+        .snake(Left, &[(0, 0), (1, 0)])
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn hex_snake_moves_diagonally() {
+    use super::{
+        renderer::GameDisplayToString, topology, BuildError, GameBuilder, MovementDirection::*,
+    };
+
+    let hex = || topology::by_name("hex").unwrap();
+    assert_eq!(
+        GameBuilder::new(6, 4)
+            .topology(hex())
+            .direction(Up)
+            .build()
+            .err(),
+        Some(BuildError::UnsupportedDirection(Up))
+    );
+
+    // (1, 1) and (1, 2) are neighbours on hex grid, odd row is shifted right
+    let mut game = GameBuilder::new(4, 4)
+        .topology(hex())
+        .snake(UpRight, &[(1, 1), (1, 2)])
+        .fruit_policy(NullFruit)
+        .build()
+        .unwrap();
+    assert!(game.set_snake_direction(Up).is_err());
+    game.try_move().unwrap();
+    assert_eq!(game.snake().tiles(), vec![2, 5]);
+
+    let output = game.render(&GameDisplayToString).unwrap();
+    let expected = "###########\n\r\
+                    #    ██   #\n\r\
+                    #   ██    #\n\r\
+                    #         #\n\r\
+                    #         #\n\r\
+                    ###########";
+    assert_eq!(expected, output);
+}
+
+#[test]
+fn mobius_edge_turns_snake_upside_down() {
+    use super::{snake::Boundary, GameBuilder, MovementDirection::*};
+
+    let builder = || {
+        GameBuilder::new(5, 4)
+            .topology(super::topology::by_name("square8").unwrap())
+            .boundary(Boundary::Mobius)
+            .fruit_policy(NullFruit)
+    };
+    // top and bottom edges of mobius strip are solid
+    assert!(builder().snake(Right, &[(4, 0), (3, 3)]).build().is_err());
+
+    let mut game = builder().snake(UpRight, &[(4, 1), (3, 2)]).build().unwrap();
+    game.try_move().unwrap();
+
+    // head moved over right edge from (4, 1) to (0, 4 - 1 - 0)
+    assert_eq!(game.snake().tiles()[0], 3 * 5);
+    assert_eq!(game.snake().direction(), DownRight);
+}
@@ -0,0 +1,337 @@
+use super::{
+    MovementDirection::{self, *},
+    Turn,
+};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Shape of level grid: which tiles are next to each other and how tiles are laid out in text.
+///
+/// Level edges are handled separately by [`Boundary`].
+pub trait Topology: Send + Sync {
+    /// short name, suited for configuration and API
+    fn name(&self) -> &'static str;
+
+    /// directions snake can move in
+    fn directions(&self) -> &'static [MovementDirection];
+
+    /// Offset of tile next to tile on (x, y) in given direction,
+    /// `None` when direction is not supported.
+    fn offset(&self, x: usize, y: usize, direction: MovementDirection) -> Option<(isize, isize)>;
+
+    /// Number of text columns taken by each tile.
+    fn cell_width(&self) -> usize {
+        1
+    }
+
+    /// Text column and row of tile on (x, y).
+    fn text_cell(&self, x: usize, y: usize) -> (usize, usize) {
+        (x * self.cell_width(), y)
+    }
+
+    /// Number of text columns of level with given width.
+    fn text_columns(&self, width: usize) -> usize {
+        width * self.cell_width()
+    }
+
+    fn supports(&self, direction: MovementDirection) -> bool {
+        self.directions().contains(&direction)
+    }
+
+    /// Neighbours of tile depend on parity of its row.
+    fn shifted_rows(&self) -> bool {
+        false
+    }
+
+    /// Direction after turning to the nearest supported direction on given side
+    /// (90 degrees on square grid, 60 on hex grid, 45 with diagonal moves).
+    fn turn(&self, direction: MovementDirection, turn: Turn) -> MovementDirection {
+        let len = COUNTERCLOCKWISE.len();
+        let position = match COUNTERCLOCKWISE.iter().position(|d| *d == direction) {
+            Some(position) => position,
+            None => return direction,
+        };
+        let offset = |n| match turn {
+            Turn::Left => n,
+            Turn::Right => len - n,
+            Turn::Straight => 0,
+        };
+
+        (1..len)
+            .map(|n| COUNTERCLOCKWISE[(position + offset(n)) % len])
+            .find(|d| self.supports(*d))
+            .filter(|_| turn != Turn::Straight)
+            .unwrap_or(direction)
+    }
+}
+
+/// All directions ordered counterclockwise, starting with `Right`.
+const COUNTERCLOCKWISE: [MovementDirection; 8] =
+    [Right, UpRight, Up, UpLeft, Left, DownLeft, Down, DownRight];
+
+/// Names of built-in topologies.
+pub const TOPOLOGIES: [&str; 3] = ["square", "square8", "hex"];
+
+/// Built-in topology by its name.
+pub fn by_name(name: &str) -> Option<Arc<dyn Topology>> {
+    match name {
+        "square" => Some(Arc::new(Square)),
+        "square8" => Some(Arc::new(Square8)),
+        "hex" => Some(Arc::new(Hex)),
+        _ => None,
+    }
+}
+
+/// Square grid with 4 directions.
+pub struct Square;
+
+impl Topology for Square {
+    fn name(&self) -> &'static str {
+        "square"
+    }
+
+    fn directions(&self) -> &'static [MovementDirection] {
+        &[Up, Down, Left, Right]
+    }
+
+    fn offset(&self, _: usize, _: usize, direction: MovementDirection) -> Option<(isize, isize)> {
+        match direction {
+            Up => Some((0, -1)),
+            Down => Some((0, 1)),
+            Left => Some((-1, 0)),
+            Right => Some((1, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Square grid with diagonal moves (8 directions).
+pub struct Square8;
+
+impl Topology for Square8 {
+    fn name(&self) -> &'static str {
+        "square8"
+    }
+
+    fn directions(&self) -> &'static [MovementDirection] {
+        &[Up, Down, Left, Right, UpLeft, UpRight, DownLeft, DownRight]
+    }
+
+    fn offset(&self, x: usize, y: usize, direction: MovementDirection) -> Option<(isize, isize)> {
+        match direction {
+            UpLeft => Some((-1, -1)),
+            UpRight => Some((1, -1)),
+            DownLeft => Some((-1, 1)),
+            DownRight => Some((1, 1)),
+            _ => Square.offset(x, y, direction),
+        }
+    }
+}
+
+/// Hexagonal grid with odd rows shifted half a tile right (6 directions).
+///
+/// Levels with odd height do not wrap consistently over top and bottom edge.
+pub struct Hex;
+
+impl Topology for Hex {
+    fn name(&self) -> &'static str {
+        "hex"
+    }
+
+    fn directions(&self) -> &'static [MovementDirection] {
+        &[Left, Right, UpLeft, UpRight, DownLeft, DownRight]
+    }
+
+    fn offset(&self, _: usize, y: usize, direction: MovementDirection) -> Option<(isize, isize)> {
+        // diagonal neighbours of even rows are shifted left
+        let shift = (y % 2) as isize;
+        match direction {
+            Left => Some((-1, 0)),
+            Right => Some((1, 0)),
+            UpLeft => Some((shift - 1, -1)),
+            UpRight => Some((shift, -1)),
+            DownLeft => Some((shift - 1, 1)),
+            DownRight => Some((shift, 1)),
+            _ => None,
+        }
+    }
+
+    fn cell_width(&self) -> usize {
+        2
+    }
+
+    fn text_cell(&self, x: usize, y: usize) -> (usize, usize) {
+        (2 * x + y % 2, y)
+    }
+
+    fn text_columns(&self, width: usize) -> usize {
+        2 * width + 1
+    }
+
+    fn shifted_rows(&self) -> bool {
+        true
+    }
+}
+
+/// What happens when snake moves over level edge.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Boundary {
+    /// snake continues from opposite level edge (torus)
+    #[default]
+    Wrap,
+    /// level edge is a wall
+    Solid,
+    /// left and right edges are joined upside down, top and bottom edges are walls
+    Mobius,
+    /// left and right edges are joined upside down, top and bottom edges wrap (Klein bottle)
+    Klein,
+}
+
+impl Boundary {
+    /// Position (with movement direction) on level of given size where snake moving
+    /// to (x, y) ends up, `None` when it hits the level edge.
+    pub fn wrap(
+        &self,
+        width: usize,
+        height: usize,
+        x: isize,
+        y: isize,
+        direction: MovementDirection,
+    ) -> Option<(usize, usize, MovementDirection)> {
+        let (w, h) = (width as isize, height as isize);
+        let x_out = !(0..w).contains(&x);
+        let y_out = !(0..h).contains(&y);
+
+        match self {
+            _ if !x_out && !y_out => Some((x as usize, y as usize, direction)),
+            Self::Solid => None,
+            Self::Wrap => Some((
+                x.rem_euclid(w) as usize,
+                y.rem_euclid(h) as usize,
+                direction,
+            )),
+            Self::Mobius if y_out => None,
+            Self::Mobius | Self::Klein => {
+                let y = y.rem_euclid(h);
+                if x_out {
+                    let flipped = (h - 1 - y) as usize;
+                    Some((x.rem_euclid(w) as usize, flipped, direction.flipped()))
+                } else {
+                    Some((x as usize, y as usize, direction))
+                }
+            }
+        }
+    }
+
+    /// Level edges keep neighbours of tiles consistent on given topology and level height.
+    /// On grids with shifted rows, wrapped top and bottom edges need even height to keep rows
+    /// alternating, while side edges joined upside down move tiles between even and odd rows
+    /// unless height is odd. Klein bottle needs both, so it never fits such grids.
+    pub fn fits(&self, topology: &dyn Topology, height: usize) -> bool {
+        if !topology.shifted_rows() {
+            return true;
+        }
+        match self {
+            Self::Wrap => height.is_multiple_of(2),
+            Self::Mobius => !height.is_multiple_of(2),
+            Self::Klein => false,
+            Self::Solid => true,
+        }
+    }
+
+    /// short name, suited for configuration and API
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wrap => "wrap",
+            Self::Solid => "solid",
+            Self::Mobius => "mobius",
+            Self::Klein => "klein",
+        }
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" | "torus" => Ok(Self::Wrap),
+            "solid" => Ok(Self::Solid),
+            "mobius" => Ok(Self::Mobius),
+            "klein" => Ok(Self::Klein),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Boundary, Hex, Square, Square8, Topology};
+    use crate::snake_game::{MovementDirection::*, Turn};
+
+    #[test]
+    fn turns_follow_grid_directions() {
+        assert_eq!(Square.turn(Down, Turn::Left), Right);
+        assert_eq!(Square.turn(Down, Turn::Right), Left);
+        assert_eq!(Square.turn(Right, Turn::Left), Up);
+        assert_eq!(Square.turn(Right, Turn::Straight), Right);
+        assert_eq!(Square8.turn(Right, Turn::Left), UpRight);
+        assert_eq!(Square8.turn(Up, Turn::Right), UpRight);
+        assert_eq!(Hex.turn(Right, Turn::Left), UpRight);
+        assert_eq!(Hex.turn(UpRight, Turn::Left), UpLeft);
+        assert_eq!(Hex.turn(DownLeft, Turn::Right), Left);
+    }
+
+    #[test]
+    fn hex_neighbours_depend_on_row() {
+        assert_eq!(Hex.offset(3, 2, UpLeft), Some((-1, -1)));
+        assert_eq!(Hex.offset(3, 2, DownRight), Some((0, 1)));
+        assert_eq!(Hex.offset(3, 3, UpLeft), Some((0, -1)));
+        assert_eq!(Hex.offset(3, 3, DownRight), Some((1, 1)));
+        assert_eq!(Hex.offset(3, 3, Up), None);
+
+        // moving back returns to the same tile
+        for y in 0..2 {
+            for direction in Hex.directions() {
+                let (dx, dy) = Hex.offset(3, y, *direction).unwrap();
+                let (x2, y2) = ((3 + dx) as usize, (y as isize + dy + 2) as usize);
+                let (bx, by) = Hex.offset(x2, y2, direction.opposite()).unwrap();
+                assert_eq!((x2 as isize + bx, y2 as isize + by - 2), (3, y as isize));
+            }
+        }
+    }
+
+    #[test]
+    fn non_orientable_edges_flip_snake() {
+        assert_eq!(Boundary::Wrap.wrap(5, 4, 5, 1, Right), Some((0, 1, Right)));
+        assert_eq!(Boundary::Solid.wrap(5, 4, 5, 1, Right), None);
+        assert_eq!(
+            Boundary::Mobius.wrap(5, 4, 5, 1, Right),
+            Some((0, 2, Right))
+        );
+        assert_eq!(
+            Boundary::Mobius.wrap(5, 4, -1, 0, DownLeft),
+            Some((4, 3, UpLeft))
+        );
+        assert_eq!(Boundary::Mobius.wrap(5, 4, 2, 4, Down), None);
+        assert_eq!(Boundary::Klein.wrap(5, 4, 2, 4, Down), Some((2, 0, Down)));
+        assert_eq!(
+            Boundary::Klein.wrap(5, 4, 5, -1, UpRight),
+            Some((0, 0, DownRight))
+        );
+    }
+
+    #[test]
+    fn flipped_edges_keep_hex_row_parity() {
+        assert!(Boundary::Wrap.fits(&Hex, 4));
+        assert!(!Boundary::Wrap.fits(&Hex, 5));
+        assert!(Boundary::Wrap.fits(&Square, 5));
+        assert!(Boundary::Mobius.fits(&Square, 4));
+        assert!(Boundary::Mobius.fits(&Hex, 5));
+        assert!(!Boundary::Mobius.fits(&Hex, 4));
+        assert!(Boundary::Klein.fits(&Square, 5));
+        assert!(!Boundary::Klein.fits(&Hex, 4));
+        assert!(!Boundary::Klein.fits(&Hex, 5));
+        assert!(Boundary::Solid.fits(&Hex, 5));
+    }
+}
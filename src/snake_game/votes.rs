@@ -0,0 +1,511 @@
+use super::{
+    controller::Controller, helper, topology::Topology, FruitBehavior, Game, GameError,
+    MovementDirection, SnakeBehavior, Turn, TurnError,
+};
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+
+/// max number of votes drawn from all votes collected during tick
+const VOTES_SAMPLE_SIZE: usize = 5;
+/// percent of active voters required by `quorum` strategy without explicit value
+const DEFAULT_QUORUM: u8 = 50;
+
+/// Vote for absolute direction or turn relative to snake movement.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Vote {
+    Absolute(MovementDirection),
+    Relative(Turn),
+}
+
+impl Vote {
+    /// Direction voted for when snake moves in `current_direction`.
+    pub fn resolve(
+        &self,
+        current_direction: MovementDirection,
+        topology: &dyn Topology,
+    ) -> MovementDirection {
+        match self {
+            Self::Absolute(direction) => *direction,
+            Self::Relative(turn) => topology.turn(current_direction, *turn),
+        }
+    }
+}
+
+/// Allowed voting style.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Steering {
+    /// both absolute and relative votes
+    #[default]
+    Any,
+    Absolute,
+    Relative,
+}
+
+impl Steering {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::Absolute => "absolute",
+            Self::Relative => "relative",
+        }
+    }
+
+    pub fn allows(&self, vote: &Vote) -> bool {
+        matches!(
+            (self, vote),
+            (Self::Any, _)
+                | (Self::Absolute, Vote::Absolute(_))
+                | (Self::Relative, Vote::Relative(_))
+        )
+    }
+}
+
+impl std::str::FromStr for Steering {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(Self::Any),
+            "absolute" => Ok(Self::Absolute),
+            "relative" => Ok(Self::Relative),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How movement is selected from votes collected during tick.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Strategy {
+    /// most votes among few drawn randomly
+    #[default]
+    Sample,
+    /// most votes of all, only when given percent of active voters voted on the tick
+    Quorum(u8),
+}
+
+impl Strategy {
+    /// Select movement, see [`select_move`], with distinct voters of the tick.
+    pub fn select(
+        &self,
+        tick: u64,
+        votes: Vec<Vote>,
+        current_direction: MovementDirection,
+        topology: &dyn Topology,
+        turnout: Turnout,
+    ) -> VoteResult {
+        let mut result = match self {
+            Self::Sample => select_move(tick, votes, current_direction, topology),
+            Self::Quorum(percent) => {
+                let required = (turnout.active * *percent as usize).div_ceil(100).max(1);
+                let mut result = pick(tick, votes, current_direction, topology, usize::MAX);
+                if turnout.voters < required {
+                    result.winner = None;
+                    result.tie_break = false;
+                }
+                result.quorum = Some(required);
+                result
+            }
+        };
+        result.voters = turnout.voters;
+        result.active_voters = turnout.active;
+        result
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sample => write!(f, "sample"),
+            Self::Quorum(percent) => write!(f, "quorum:{percent}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "sample" => Ok(Self::Sample),
+            None if s == "quorum" => Ok(Self::Quorum(DEFAULT_QUORUM)),
+            Some(("quorum", percent)) => match percent.parse() {
+                Ok(percent @ 1..=100) => Ok(Self::Quorum(percent)),
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+/// Distinct voters of single tick and all recently active voters.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Turnout {
+    pub voters: usize,
+    pub active: usize,
+}
+
+/// Outcome of votes collected during single game tick.
+#[derive(Clone, serde::Serialize)]
+pub struct VoteResult {
+    pub tick: u64,
+    /// votes drawn for selecting movement
+    pub sampled: Vec<MovementDirection>,
+    /// selected movement, `None` when there was no valid vote
+    pub winner: Option<MovementDirection>,
+    /// winner was picked randomly from directions with equal votes count
+    pub tie_break: bool,
+    /// votes dropped because they were opposite to snake movement (to last buffered turn of single player)
+    pub discarded: usize,
+    /// number of relative votes (turns), counted with absolute votes they resolved to
+    pub relative: usize,
+    /// distinct voters of the tick
+    pub voters: usize,
+    /// voters active recently
+    pub active_voters: usize,
+    /// voters required by quorum strategy, winner is `None` when not reached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<usize>,
+}
+
+/// Steers snake of crowd played game on every tick: movement is selected from votes, votes of
+/// single player are queued as successive turns and AI controller steers when nobody votes.
+pub struct CrowdDriver<S: SnakeBehavior, F: FruitBehavior> {
+    ai_controller: Box<dyn Controller<S, F> + Send>,
+    /// ticks without votes after which AI controller steers the snake
+    ai_fallback_ticks: u64,
+    ticks_without_votes: u64,
+}
+
+impl<S: SnakeBehavior, F: FruitBehavior> CrowdDriver<S, F> {
+    pub fn new(ai_controller: Box<dyn Controller<S, F> + Send>, ai_fallback_ticks: u64) -> Self {
+        Self {
+            ai_controller,
+            ai_fallback_ticks,
+            ticks_without_votes: 0,
+        }
+    }
+
+    /// Nobody voted for long enough, snake is steered by AI controller.
+    pub fn ai_steering(&self) -> bool {
+        self.ticks_without_votes >= self.ai_fallback_ticks
+    }
+
+    /// Steer snake with votes collected for the tick, called before game move.
+    pub fn steer(
+        &mut self,
+        game: &mut Game<S, F>,
+        strategy: &Strategy,
+        tick: u64,
+        votes: Vec<Vote>,
+        turnout: Turnout,
+    ) -> Result<VoteResult, GameError> {
+        // single player steers like bot driving alone, successive turns are buffered
+        let single_player = turnout.active == 1 && turnout.voters <= 1;
+        // buffered turns are accepted, only turns opposite to queued direction are discarded
+        let mut buffered = false;
+        let mut discarded = 0;
+        if single_player {
+            for vote in &votes {
+                let movement = vote.resolve(game.last_queued_direction(), game.level().topology());
+                match game.queue_direction(movement) {
+                    Ok(()) => buffered = true,
+                    Err(TurnError::Opposite) => discarded += 1,
+                    // unchanged turns and turns over buffer depth are dropped
+                    Err(_) => (),
+                }
+            }
+        } else {
+            game.clear_input_buffer();
+        }
+
+        let mut vote_result = strategy.select(
+            tick,
+            votes,
+            game.snake().direction(),
+            game.level().topology(),
+            turnout,
+        );
+        if single_player {
+            vote_result.discarded = discarded;
+        }
+        match vote_result.winner {
+            Some(movement) => {
+                self.ticks_without_votes = 0;
+                if !single_player {
+                    game.set_snake_direction(movement)?;
+                }
+            }
+            // turns opposite to current direction are not selected
+            None if buffered => self.ticks_without_votes = 0,
+            None => {
+                self.ticks_without_votes += 1;
+                // keep the board alive when nobody plays
+                if self.ai_steering() {
+                    let movement = self.ai_controller.next_direction(game);
+                    game.set_snake_direction(movement)?;
+                }
+            }
+        }
+        Ok(vote_result)
+    }
+
+    /// Queue turns of bot driving the snake alone, votes are not counted.
+    /// Turns rejected by game input buffer are dropped.
+    pub fn steer_by_bot(&mut self, game: &mut Game<S, F>, turns: Vec<MovementDirection>) {
+        for movement in turns {
+            game.queue_direction(movement).ok();
+        }
+    }
+}
+
+/// Select movement from votes collected during tick,
+/// relative votes are resolved against current snake direction.
+pub fn select_move(
+    tick: u64,
+    votes: Vec<Vote>,
+    current_direction: MovementDirection,
+    topology: &dyn Topology,
+) -> VoteResult {
+    pick(tick, votes, current_direction, topology, VOTES_SAMPLE_SIZE)
+}
+
+/// Most voted movement among `sample_size` votes drawn randomly.
+fn pick(
+    tick: u64,
+    votes: Vec<Vote>,
+    current_direction: MovementDirection,
+    topology: &dyn Topology,
+    sample_size: usize,
+) -> VoteResult {
+    let total = votes.len();
+    let relative = votes
+        .iter()
+        .filter(|v| matches!(v, Vote::Relative(_)))
+        .count();
+    let available_moves = votes
+        .into_iter()
+        .map(|v| v.resolve(current_direction, topology))
+        .filter(|d| !d.is_opposite_to(&current_direction))
+        .collect::<Vec<_>>();
+
+    let mut result = VoteResult {
+        tick,
+        sampled: Vec::new(),
+        winner: None,
+        tie_break: false,
+        discarded: total - available_moves.len(),
+        relative,
+        voters: 0,
+        active_voters: 0,
+        quorum: None,
+    };
+
+    if available_moves.is_empty() {
+        return result;
+    }
+
+    let mut r = rand::thread_rng();
+
+    // pick random sample (five by default)
+    result.sampled = available_moves
+        .choose_multiple(&mut r, sample_size)
+        .cloned()
+        .collect();
+    let (most_occurrences, mut selected_count) =
+        helper::get_most_move_occurrences_in(result.sampled.iter().cloned());
+
+    // retain moves with most occurrences
+    let selected = selected_count
+        .drain()
+        .filter_map(|(mov, count)| {
+            if count == most_occurrences {
+                Some(mov)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // finally pick movement
+    result.tie_break = selected.len() > 1;
+    result.winner = selected.choose(&mut r).cloned();
+
+    result
+}
+
+/// Count of pending votes for every movement direction of level topology,
+/// relative votes are counted as direction they resolve to for current snake direction.
+pub fn tally(
+    votes: &[Vote],
+    current_direction: MovementDirection,
+    topology: &dyn Topology,
+) -> HashMap<MovementDirection, usize> {
+    let mut tally = topology
+        .directions()
+        .iter()
+        .map(|d| (*d, 0))
+        .collect::<HashMap<_, _>>();
+    for vote in votes {
+        *tally
+            .entry(vote.resolve(current_direction, topology))
+            .or_default() += 1;
+    }
+
+    tally
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MovementDirection::*, Strategy, Turnout, Vote};
+    use crate::snake_game::{topology::Square, Turn};
+
+    fn absolute(directions: &[crate::snake_game::MovementDirection]) -> Vec<Vote> {
+        directions.iter().map(|d| Vote::Absolute(*d)).collect()
+    }
+
+    #[test]
+    fn opposite_votes_are_discarded() {
+        let result = super::select_move(1, absolute(&[Left, Left, Up, Left]), Right, &Square);
+
+        assert_eq!(result.discarded, 3);
+        assert_eq!(result.sampled, vec![Up]);
+        assert_eq!(result.winner, Some(Up));
+        assert!(!result.tie_break);
+    }
+
+    #[test]
+    fn tie_break_between_equal_votes() {
+        let result = super::select_move(1, absolute(&[Up, Down]), Right, &Square);
+
+        assert_eq!(result.discarded, 0);
+        assert!(result.tie_break);
+        assert!(matches!(result.winner, Some(Up) | Some(Down)));
+    }
+
+    #[test]
+    fn relative_votes_merge_with_absolute() {
+        // snake heading down, turning left means moving right
+        let mut votes = absolute(&[Right, Left]);
+        votes.push(Vote::Relative(Turn::Left));
+        let result = super::select_move(1, votes, Down, &Square);
+
+        assert_eq!(result.relative, 1);
+        assert_eq!(result.winner, Some(Right));
+        assert!(!result.tie_break);
+    }
+
+    #[test]
+    fn quorum_requires_share_of_active_voters() {
+        let quorum: Strategy = "quorum:60".parse().unwrap();
+        let votes = absolute(&[Up, Up, Up, Up, Up, Up, Down]);
+        let turnout = |voters| Turnout { voters, active: 5 };
+
+        let missed = quorum.select(1, votes.clone(), Right, &Square, turnout(2));
+        assert_eq!((missed.quorum, missed.winner), (Some(3), None));
+        assert_eq!(missed.active_voters, 5);
+
+        // all votes are counted, not a sample
+        let reached = quorum.select(1, votes, Right, &Square, turnout(3));
+        assert_eq!(reached.sampled.len(), 7);
+        assert_eq!(reached.winner, Some(Up));
+
+        assert_eq!("quorum".parse(), Ok(Strategy::Quorum(50)));
+        assert!("quorum:0".parse::<Strategy>().is_err());
+        assert_eq!(Strategy::Quorum(60).to_string(), "quorum:60");
+    }
+
+    #[test]
+    fn pending_tally_contains_all_directions() {
+        let mut votes = absolute(&[Up, Up, Left]);
+        votes.push(Vote::Relative(Turn::Straight));
+        let tally = super::tally(&votes, Right, &Square);
+
+        assert_eq!(tally.len(), 4);
+        assert_eq!(tally[&Up], 2);
+        assert_eq!(tally[&Left], 1);
+        assert_eq!(tally[&Right], 1);
+        assert_eq!(tally[&Down], 0);
+    }
+
+    #[test]
+    fn single_player_turns_are_buffered_and_ai_steers_without_votes() {
+        use super::CrowdDriver;
+        use crate::snake_game::{controller::GreedyFruit, testing::NullFruit, GameBuilder};
+
+        let mut game = GameBuilder::new(10, 10)
+            .snake(Right, &[(4, 4), (3, 4)])
+            .fruit_policy(NullFruit)
+            .build()
+            .unwrap();
+        game.set_input_buffer(3);
+        let mut driver = CrowdDriver::new(Box::new(GreedyFruit), 2);
+        let single = Turnout {
+            voters: 1,
+            active: 1,
+        };
+
+        let votes = vec![Vote::Absolute(Up), Vote::Relative(Turn::Left)];
+        let result = driver
+            .steer(&mut game, &Strategy::Sample, 1, votes, single)
+            .unwrap();
+        assert!(result.winner.is_some());
+        // turn left after going up
+        assert_eq!(game.queued_turns(), 2);
+        assert_eq!(game.last_queued_direction(), Left);
+
+        let crowd = Turnout {
+            voters: 0,
+            active: 2,
+        };
+        driver
+            .steer(&mut game, &Strategy::Sample, 2, Vec::new(), crowd)
+            .unwrap();
+        assert_eq!(game.queued_turns(), 0);
+        assert!(!driver.ai_steering());
+        driver
+            .steer(&mut game, &Strategy::Sample, 3, Vec::new(), crowd)
+            .unwrap();
+        assert!(driver.ai_steering());
+    }
+
+    #[test]
+    fn buffered_single_player_turns_are_not_discarded() {
+        use super::CrowdDriver;
+        use crate::snake_game::{controller::GreedyFruit, testing::NullFruit, GameBuilder};
+
+        let mut game = GameBuilder::new(10, 10)
+            .snake(Right, &[(4, 4), (3, 4)])
+            .fruit_policy(NullFruit)
+            .build()
+            .unwrap();
+        game.set_input_buffer(3);
+        let mut driver = CrowdDriver::new(Box::new(GreedyFruit), 1);
+        let single = Turnout {
+            voters: 1,
+            active: 1,
+        };
+
+        let votes = vec![Vote::Absolute(Up)];
+        driver
+            .steer(&mut game, &Strategy::Sample, 1, votes, single)
+            .unwrap();
+
+        // left is opposite to current direction, but not to buffered up
+        let votes = vec![Vote::Absolute(Left)];
+        let result = driver
+            .steer(&mut game, &Strategy::Sample, 2, votes, single)
+            .unwrap();
+        assert_eq!(result.winner, None);
+        assert_eq!(result.discarded, 0);
+        assert_eq!(game.queued_turns(), 2);
+        assert!(!driver.ai_steering());
+
+        let votes = vec![Vote::Absolute(Right)];
+        let result = driver
+            .steer(&mut game, &Strategy::Sample, 3, votes, single)
+            .unwrap();
+        assert_eq!(result.discarded, 1);
+        assert_eq!(game.last_queued_direction(), Left);
+        assert!(!driver.ai_steering());
+    }
+}
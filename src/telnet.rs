@@ -0,0 +1,398 @@
+use crate::listen::ACCEPT_ERROR_DELAY;
+use crate::votes::{Steering, Vote};
+use crate::{auth::Role, presence::Client, AppState, StreamFrame};
+use snapi::snake_game::{
+    renderer::tile_char, topology::Topology, MovementDirection, TileChange, Turn,
+};
+use std::fmt::Write as _;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast::error::RecvError, Semaphore};
+
+/// max number of connected telnet clients
+const MAX_CLIENTS: usize = 64;
+const DIRECTION_KEYS_HELP: &str = "w/a/s/d or arrows to vote (q/e/z/c diagonal)";
+const TURN_KEYS_HELP: &str = "j/l to turn left/right, k straight";
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+/// server echoes nothing and suppresses go-ahead, so client sends every key immediately
+const CHARACTER_MODE: [u8; 6] = [IAC, WILL, ECHO, IAC, WILL, SUPPRESS_GO_AHEAD];
+
+// telnet commands and options
+const IAC: u8 = 255;
+const WILL: u8 = 251;
+const DONT: u8 = 254;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const IP: u8 = 244;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// Address from `SNAPI_TELNET` env variable, `None` when telnet is disabled.
+pub fn addr_from_env() -> Result<Option<SocketAddr>, String> {
+    match std::env::var("SNAPI_TELNET") {
+        Ok(addr) => addr
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid telnet address: {addr}")),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Accept telnet clients, each one is served by its own task.
+pub fn spawn(app: Arc<AppState>, listener: TcpListener) {
+    let slots = Arc::new(Semaphore::new(MAX_CLIENTS));
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    eprintln!("Accepting telnet connection failed: {err}");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            let Ok(slot) = Arc::clone(&slots).try_acquire_owned() else {
+                stream
+                    .write_all(b"Too many players, try again later.\r\n")
+                    .await
+                    .ok();
+                continue;
+            };
+
+            let app = Arc::clone(&app);
+            tokio::spawn(async move {
+                let client = Client::from_addr(peer.ip());
+                app.presence.lock().await.stream_opened(&client);
+                if let Err(err) = session(&app, &mut stream, peer.ip()).await {
+                    // client went away
+                    if err.kind() != io::ErrorKind::BrokenPipe {
+                        eprintln!("Telnet client {peer} failed: {err}");
+                    }
+                }
+                stream.write_all(SHOW_CURSOR.as_bytes()).await.ok();
+                app.presence.lock().await.stream_closed(&client);
+                drop(slot);
+            });
+        }
+    });
+}
+
+/// Show live board redrawn on every tick and vote with pressed keys.
+async fn session(app: &AppState, stream: &mut TcpStream, ip: IpAddr) -> io::Result<()> {
+    let role = app.auth.anonymous();
+    if role.is_none() {
+        return stream
+            .write_all(b"Authentication required, use HTTP API.\r\n")
+            .await;
+    }
+    let client = Client::from_addr(ip);
+    let (mut reader, mut writer) = stream.split();
+
+    // subscribe before reading current board, so no delta after it is missed
+    let mut frames = app.stream_send.subscribe();
+    let mut screen = Screen {
+        help: keys_help(app.steering),
+        ..Default::default()
+    };
+    writer.write_all(&CHARACTER_MODE).await?;
+    writer.write_all(screen.redraw(app).as_bytes()).await?;
+
+    let mut input = Input::default();
+    let mut buf = [0; 64];
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => {
+                let read = read?;
+                if read == 0 {
+                    return Ok(());
+                }
+                for key in buf[..read].iter().filter_map(|b| input.feed(*b)) {
+                    let vote = match key {
+                        Key::Quit => return Ok(()),
+                        Key::Direction(direction) => Vote::Absolute(direction),
+                        Key::Turn(turn) => Vote::Relative(turn),
+                    };
+                    let feedback = if role < Some(Role::Voter) {
+                        "Voting requires authentication, use HTTP API.".to_owned()
+                    } else {
+                        submit(app, &client, vote).await
+                    };
+                    writer.write_all(screen.feedback(&feedback).as_bytes()).await?;
+                }
+            }
+            frame = frames.recv() => {
+                let output = match frame {
+                    Ok(StreamFrame::Delta(delta)) => screen.paint(delta.game, delta.tick, &delta.changes),
+                    // new game or client too slow, start over
+                    Ok(StreamFrame::State(_)) | Err(RecvError::Lagged(_)) => screen.redraw(app),
+                    Err(RecvError::Closed) => return Ok(()),
+                };
+                writer.write_all(output.as_bytes()).await?;
+            }
+        }
+    }
+}
+
+/// Keys of voting styles allowed by steering.
+fn keys_help(steering: Steering) -> String {
+    match steering {
+        Steering::Any => format!("{DIRECTION_KEYS_HELP}, {TURN_KEYS_HELP}"),
+        Steering::Absolute => DIRECTION_KEYS_HELP.to_owned(),
+        Steering::Relative => TURN_KEYS_HELP.to_owned(),
+    }
+}
+
+async fn submit(app: &AppState, client: &Client, vote: Vote) -> String {
+    // same commands as HTTP endpoints
+    let command = match vote {
+        Vote::Absolute(direction) => {
+            if !app.topology.supports(direction) {
+                return format!(
+                    "{}: direction not supported by level topology",
+                    direction.name()
+                );
+            }
+            direction.name()
+        }
+        Vote::Relative(Turn::Left) => "turn/left",
+        Vote::Relative(Turn::Right) => "turn/right",
+        Vote::Relative(Turn::Straight) => "straight",
+    };
+    match crate::submit_vote(app, client, vote, command.to_owned()).await {
+        Ok(accepted) => format!(
+            "Voted {} for tick {}",
+            accepted.direction.name(),
+            accepted.target_tick
+        ),
+        Err((_, rejected)) => format!("{command}: {}", rejected.error),
+    }
+}
+
+/// Board drawn on client terminal.
+#[derive(Default)]
+struct Screen {
+    /// game and tick of drawn board
+    drawn: (u64, u64),
+    /// text rows taken by board
+    rows: usize,
+    cell_width: usize,
+    topology: Option<Arc<dyn Topology>>,
+    /// voting keys shown in status line
+    help: String,
+}
+
+impl Screen {
+    /// Whole screen with board of current state.
+    fn redraw(&mut self, app: &AppState) -> String {
+        // board and its tick are taken from the same state, so no delta is skipped or repeated
+        let current = app
+            .state_recv
+            .borrow()
+            .as_ref()
+            .and_then(|s| Some(((s.game, s.tick), s.state.tile_frame()?)));
+        let Some((drawn, frame)) = current else {
+            return format!("{CLEAR_SCREEN}Waiting for game...\r\n");
+        };
+        let board = frame.to_text().unwrap_or_default();
+
+        self.drawn = drawn;
+        self.rows = frame.height + 2;
+        self.cell_width = frame.topology.cell_width();
+        self.topology = Some(frame.topology);
+
+        let mut output = format!("{HIDE_CURSOR}{CLEAR_SCREEN}{board}");
+        output.push_str(&self.status());
+        output
+    }
+
+    /// Tiles changed on tick painted over drawn board.
+    fn paint(&mut self, game: u64, tick: u64, changes: &[TileChange]) -> String {
+        let mut output = String::new();
+        // already included in drawn board
+        if (game, tick) <= self.drawn {
+            return output;
+        }
+        let Some(topology) = &self.topology else {
+            return output;
+        };
+
+        for change in changes {
+            let (column, row) = topology.text_cell(change.x, change.y);
+            let cell = tile_char(change.tile).to_string().repeat(self.cell_width);
+            // board is framed with walls, terminal rows and columns count from 1
+            write!(output, "\x1b[{};{}H{cell}", row + 2, column + 2).ok();
+        }
+        self.drawn = (game, tick);
+        output.push_str(&self.status());
+        output
+    }
+
+    fn status(&self) -> String {
+        let (game, tick) = self.drawn;
+        format!(
+            "\x1b[{};1H\x1b[2KGame {game}, tick {tick} | {}, Ctrl+C to quit",
+            self.rows + 1,
+            self.help
+        )
+    }
+
+    fn feedback(&self, message: &str) -> String {
+        format!("\x1b[{};1H\x1b[2K{message}", self.rows + 2)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Key {
+    Direction(MovementDirection),
+    Turn(Turn),
+    Quit,
+}
+
+/// Reads keys from client input, skipping telnet commands.
+#[derive(Default)]
+struct Input {
+    state: InputState,
+}
+
+#[derive(Default)]
+enum InputState {
+    #[default]
+    Data,
+    /// after IAC
+    Command,
+    /// option byte after WILL, WONT, DO or DONT
+    Option,
+    /// subnegotiation until IAC SE
+    Subnegotiation,
+    SubnegotiationIac,
+    /// after ESC
+    Escape,
+    /// after ESC [
+    Sequence,
+}
+
+impl Input {
+    fn feed(&mut self, byte: u8) -> Option<Key> {
+        use MovementDirection::*;
+
+        let (state, key) = match (&self.state, byte) {
+            (InputState::Data, IAC) => (InputState::Command, None),
+            (InputState::Data, 0x1b) => (InputState::Escape, None),
+            (InputState::Data, byte) => (InputState::Data, Self::key(byte)),
+            (InputState::Command, IP) => (InputState::Data, Some(Key::Quit)),
+            (InputState::Command, WILL..=DONT) => (InputState::Option, None),
+            (InputState::Command, SB) => (InputState::Subnegotiation, None),
+            // escaped 255 or other command
+            (InputState::Command, _) | (InputState::Option, _) => (InputState::Data, None),
+            (InputState::Subnegotiation, IAC) => (InputState::SubnegotiationIac, None),
+            (InputState::Subnegotiation, _) => (InputState::Subnegotiation, None),
+            (InputState::SubnegotiationIac, SE) => (InputState::Data, None),
+            (InputState::SubnegotiationIac, _) => (InputState::Subnegotiation, None),
+            (InputState::Escape, b'[' | b'O') => (InputState::Sequence, None),
+            (InputState::Escape, _) => (InputState::Data, None),
+            (InputState::Sequence, b'A') => (InputState::Data, Some(Key::Direction(Up))),
+            (InputState::Sequence, b'B') => (InputState::Data, Some(Key::Direction(Down))),
+            (InputState::Sequence, b'C') => (InputState::Data, Some(Key::Direction(Right))),
+            (InputState::Sequence, b'D') => (InputState::Data, Some(Key::Direction(Left))),
+            // parameters of other sequences
+            (InputState::Sequence, b'0'..=b'9' | b';') => (InputState::Sequence, None),
+            (InputState::Sequence, _) => (InputState::Data, None),
+        };
+        self.state = state;
+        key
+    }
+
+    fn key(byte: u8) -> Option<Key> {
+        use MovementDirection::*;
+
+        let direction = match byte.to_ascii_lowercase() {
+            // Ctrl+C, Ctrl+D
+            3 | 4 => return Some(Key::Quit),
+            b'w' => Up,
+            b'a' => Left,
+            b's' => Down,
+            b'd' => Right,
+            b'q' => UpLeft,
+            b'e' => UpRight,
+            b'z' => DownLeft,
+            b'c' => DownRight,
+            b'j' => return Some(Key::Turn(Turn::Left)),
+            b'l' => return Some(Key::Turn(Turn::Right)),
+            b'k' => return Some(Key::Turn(Turn::Straight)),
+            _ => return None,
+        };
+        Some(Key::Direction(direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Input, Key, IAC, SB, SE, WILL};
+    use crate::AppState;
+    use snapi::snake_game::{MovementDirection::*, Turn};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn keys_are_read_without_telnet_commands() {
+        let mut input = Input::default();
+        let bytes = [
+            &[IAC, WILL, 31][..],
+            &[IAC, SB, 31, 0, 80, 0, 24, IAC, SE],
+            b"w\x1b[Dxdl",
+            b"\r\n",
+            &[3],
+        ]
+        .concat();
+        let keys = bytes
+            .iter()
+            .filter_map(|b| input.feed(*b))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            keys,
+            vec![
+                Key::Direction(Up),
+                Key::Direction(Left),
+                Key::Direction(Right),
+                Key::Turn(Turn::Right),
+                Key::Quit
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pressed_key_is_voted() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Arc::new(AppState::default());
+        super::spawn(Arc::clone(&app), listener);
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0; 256];
+        let read = stream.read(&mut buf).await.unwrap();
+        assert!(buf[..read].starts_with(&super::CHARACTER_MODE));
+
+        stream.write_all(b"w").await.unwrap();
+        let mut output = String::new();
+        while !output.contains("Voted up for tick 1") {
+            let read = stream.read(&mut buf).await.unwrap();
+            output.push_str(&String::from_utf8_lossy(&buf[..read]));
+        }
+        assert_eq!(
+            app.presence
+                .lock()
+                .await
+                .audience(std::time::Instant::now())
+                .voters,
+            1
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// Server TLS settings read from certificate chain and private key PEM files.
+pub struct TlsConfig {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsConfig {
+    /// Settings from `SNAPI_TLS_CERT` and `SNAPI_TLS_KEY` env variables,
+    /// `None` when TLS is not configured.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match (
+            std::env::var("SNAPI_TLS_CERT"),
+            std::env::var("SNAPI_TLS_KEY"),
+        ) {
+            (Ok(cert), Ok(key)) => Self::load(cert.as_ref(), key.as_ref()).map(Some),
+            (Err(_), Err(_)) => Ok(None),
+            _ => Err("Both SNAPI_TLS_CERT and SNAPI_TLS_KEY must be set".to_owned()),
+        }
+    }
+
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Self, String> {
+        let config = read_config(cert_path, key_path)?;
+        Ok(Self {
+            cert_path: cert_path.to_owned(),
+            key_path: key_path.to_owned(),
+            current: RwLock::new(Arc::new(config)),
+        })
+    }
+
+    /// Read certificate and key files again, current settings are kept on error.
+    pub fn reload(&self) -> Result<(), String> {
+        let config = read_config(&self.cert_path, &self.key_path)?;
+        *self
+            .current
+            .write()
+            .map_err(|_| "TLS settings lock poisoned")? = Arc::new(config);
+        Ok(())
+    }
+
+    /// Acceptor with current certificate.
+    pub fn acceptor(&self) -> TlsAcceptor {
+        let config = match self.current.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        TlsAcceptor::from(config)
+    }
+}
+
+fn read_config(cert_path: &Path, key_path: &Path) -> Result<ServerConfig, String> {
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|err| format!("{}: {err}", path.display()))
+    };
+
+    let certs = rustls_pemfile::certs(&mut open(cert_path)?)
+        .map_err(|err| format!("{}: {err}", cert_path.display()))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificates found", cert_path.display()));
+    }
+
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .map_err(|err| format!("{}: {err}", key_path.display()))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .ok_or_else(|| format!("{}: no private key found", key_path.display()))?;
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            certs.into_iter().map(Certificate).collect(),
+            PrivateKey(key),
+        )
+        .map_err(|err| format!("Invalid certificate or key: {err}"))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+/// Reload TLS settings on every SIGHUP.
+#[cfg(unix)]
+pub fn reload_on_sighup(config: Arc<TlsConfig>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                eprintln!("TLS reload on SIGHUP unavailable: {err}");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            match config.reload() {
+                Ok(()) => println!("TLS certificate reloaded."),
+                Err(err) => eprintln!("TLS certificate reload failed: {err}"),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_config: Arc<TlsConfig>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::TlsConfig;
+
+    fn write_pair(dir: &std::path::Path, name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_owned()]).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[test]
+    fn certificate_is_reloaded_and_kept_on_error() {
+        let dir = std::env::temp_dir().join(format!("snapi-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (cert_path, key_path) = write_pair(&dir, "localhost");
+        let config = TlsConfig::load(&cert_path, &key_path).unwrap();
+
+        write_pair(&dir, "snapi.local");
+        config.reload().unwrap();
+
+        std::fs::write(&key_path, "not a key").unwrap();
+        assert!(config.reload().unwrap_err().contains("no private key"));
+        assert!(TlsConfig::load(&dir.join("missing.pem"), &key_path).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,441 @@
+use crate::{AppState, GameEvent};
+use hmac::{Hmac, Mac};
+use hyper::{client::HttpConnector, header, Body, Client, Request, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+
+/// delivery attempts of single event, including the first one
+const DELIVERY_ATTEMPTS: u32 = 5;
+/// wait before first retry, doubled after every failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// max time for webhook to answer
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// max number of registered webhooks
+const MAX_WEBHOOKS: usize = 32;
+/// length of generated signing secrets in bytes
+const SECRET_LENGTH: usize = 32;
+
+/// Names of events webhooks can subscribe to.
+pub const EVENTS: [&str; 4] = ["game_started", "milestone", "high_score", "game_over"];
+
+/// URL receiving game events, signed with its secret.
+pub struct Webhook {
+    id: u64,
+    url: Uri,
+    secret: String,
+    /// subscribed event names, all events when empty
+    events: Vec<String>,
+}
+
+impl Webhook {
+    fn accepts(&self, event: &GameEvent) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event.name())
+    }
+}
+
+/// Registered webhook, without its secret.
+#[derive(serde::Serialize)]
+pub struct WebhookView {
+    pub id: u64,
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+impl From<&Webhook> for WebhookView {
+    fn from(webhook: &Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url.to_string(),
+            events: webhook.events.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Webhooks {
+    hooks: Vec<Arc<Webhook>>,
+    last_id: u64,
+}
+
+impl Webhooks {
+    /// Webhooks from `SNAPI_WEBHOOKS` (URLs separated with commas) signed with
+    /// `SNAPI_WEBHOOK_SECRET`, which is required with URLs.
+    pub fn from_env() -> Result<Self, String> {
+        let mut webhooks = Self::default();
+        let urls = match std::env::var("SNAPI_WEBHOOKS") {
+            Ok(urls) => urls,
+            Err(_) => return Ok(webhooks),
+        };
+        let secret = std::env::var("SNAPI_WEBHOOK_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .ok_or("SNAPI_WEBHOOK_SECRET must be set with SNAPI_WEBHOOKS")?;
+
+        for url in urls.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+            webhooks.register(url, Some(secret.clone()), Vec::new())?;
+        }
+        Ok(webhooks)
+    }
+
+    /// Add webhook, random secret is generated when not given.
+    pub fn register(
+        &mut self,
+        url: &str,
+        secret: Option<String>,
+        events: Vec<String>,
+    ) -> Result<(WebhookView, String), String> {
+        let url: Uri = url
+            .parse()
+            .map_err(|_| format!("Invalid webhook URL: {url}"))?;
+        if !matches!(url.scheme_str(), Some("http" | "https")) || url.host().is_none() {
+            return Err(format!("Webhook URL must be http(s)://host/...: {url}"));
+        }
+        if let Some(unknown) = events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+            return Err(format!("Unknown event: {unknown}"));
+        }
+        if self.hooks.len() >= MAX_WEBHOOKS {
+            return Err("Too many webhooks".to_owned());
+        }
+
+        self.last_id += 1;
+        let secret = secret
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(random_secret);
+        let webhook = Webhook {
+            id: self.last_id,
+            url,
+            secret: secret.clone(),
+            events,
+        };
+        let view = WebhookView::from(&webhook);
+        self.hooks.push(Arc::new(webhook));
+        Ok((view, secret))
+    }
+
+    /// Remove webhook, `false` when there is no webhook with given id.
+    pub fn remove(&mut self, id: u64) -> bool {
+        let count = self.hooks.len();
+        self.hooks.retain(|hook| hook.id != id);
+        self.hooks.len() < count
+    }
+
+    pub fn list(&self) -> Vec<WebhookView> {
+        self.hooks.iter().map(|hook| hook.as_ref().into()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    fn subscribed(&self, event: &GameEvent) -> Vec<Arc<Webhook>> {
+        self.hooks
+            .iter()
+            .filter(|hook| hook.accepts(event))
+            .cloned()
+            .collect()
+    }
+}
+
+fn random_secret() -> String {
+    use rand::RngCore;
+
+    let mut secret = [0; SECRET_LENGTH];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Hex HMAC-SHA256 of payload.
+fn signature(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(payload);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Event with time it happened, sent as JSON body.
+#[derive(serde::Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a GameEvent,
+    /// unix time
+    timestamp: u64,
+}
+
+/// Deliver game events to webhooks in background, each delivery runs in its own
+/// task, so slow webhooks delay neither the game nor each other.
+pub fn spawn(app: Arc<AppState>) {
+    let mut events = app.events.subscribe();
+    // certificates of https webhooks are verified with Mozilla root certificates
+    let client = Client::builder().build(
+        HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build(),
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(count)) => {
+                    eprintln!("Webhooks missed {count} game events.");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            let hooks = app.webhooks.lock().await.subscribed(&event);
+            if hooks.is_empty() {
+                continue;
+            }
+            let payload = Payload {
+                event: &event,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+            };
+            let body = match serde_json::to_vec(&payload) {
+                Ok(body) => Arc::new(body),
+                Err(err) => {
+                    eprintln!("Webhook payload failed: {err}");
+                    continue;
+                }
+            };
+            for hook in hooks {
+                tokio::spawn(deliver(
+                    client.clone(),
+                    hook,
+                    event.name(),
+                    Arc::clone(&body),
+                ));
+            }
+        }
+    });
+}
+
+/// Post event to webhook, retrying with backoff on connection errors,
+/// timeouts and server errors.
+async fn deliver(
+    client: Client<HttpsConnector<HttpConnector>>,
+    hook: Arc<Webhook>,
+    event: &'static str,
+    body: Arc<Vec<u8>>,
+) {
+    let signature = format!("sha256={}", signature(&hook.secret, &body));
+    let mut delay = RETRY_DELAY;
+
+    for attempt in 1..=DELIVERY_ATTEMPTS {
+        let request = Request::post(hook.url.clone())
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-snapi-event", event)
+            .header("x-snapi-signature", &signature)
+            .body(Body::from(body.as_ref().clone()));
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => return eprintln!("Webhook {} request failed: {err}", hook.id),
+        };
+
+        let error = match tokio::time::timeout(DELIVERY_TIMEOUT, client.request(request)).await {
+            Ok(Ok(response)) if response.status().is_success() => return,
+            Ok(Ok(response)) => {
+                let status = response.status();
+                // request will not get better
+                if status.is_client_error() && status != hyper::StatusCode::TOO_MANY_REQUESTS {
+                    return eprintln!("Webhook {} rejected {event}: {status}", hook.id);
+                }
+                status.to_string()
+            }
+            Ok(Err(err)) => err.to_string(),
+            Err(_) => "timeout".to_owned(),
+        };
+
+        if attempt == DELIVERY_ATTEMPTS {
+            eprintln!(
+                "Webhook {} gave up on {event} after {attempt} attempts: {error}",
+                hook.id
+            );
+        } else {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{signature, Webhooks};
+    use crate::{AppState, GameEvent};
+    use axum::{body::Bytes, http::HeaderMap, http::StatusCode, routing::post, Router};
+    use std::sync::Arc;
+    use tokio::sync::{mpsc, Mutex};
+
+    #[test]
+    fn webhooks_are_validated() {
+        let mut webhooks = Webhooks::default();
+        let (view, secret) = webhooks
+            .register("http://localhost:9000/hook", None, vec!["game_over".into()])
+            .unwrap();
+        assert_eq!(view.id, 1);
+        assert_eq!(secret.len(), 64);
+
+        assert!(webhooks
+            .register("https://example.com", None, Vec::new())
+            .is_ok());
+        assert!(webhooks
+            .register("ftp://example.com", None, Vec::new())
+            .is_err());
+        assert!(webhooks
+            .register("localhost:9000", None, Vec::new())
+            .is_err());
+        assert!(webhooks
+            .register("http://localhost", None, vec!["tick".into()])
+            .is_err());
+
+        assert!(webhooks.remove(1));
+        assert!(!webhooks.remove(1));
+        assert_eq!(webhooks.len(), 1);
+    }
+
+    /// Stand-in webhook receiver failing first request.
+    async fn receiver() -> (String, mpsc::Receiver<(HeaderMap, Bytes)>) {
+        let (send, received) = mpsc::channel(8);
+        let failures = Arc::new(Mutex::new(1));
+        let handler = move |headers: HeaderMap, body: Bytes| {
+            let send = send.clone();
+            let failures = Arc::clone(&failures);
+            async move {
+                let mut failures = failures.lock().await;
+                if *failures > 0 {
+                    *failures -= 1;
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                send.send((headers, body)).await.ok();
+                StatusCode::NO_CONTENT
+            }
+        };
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = axum::Server::from_tcp(listener).unwrap().serve(
+            Router::new()
+                .route("/hook", post(handler))
+                .into_make_service(),
+        );
+        tokio::spawn(server);
+        (url, received)
+    }
+
+    #[tokio::test]
+    async fn signed_event_is_delivered_after_retry() {
+        let (url, mut received) = receiver().await;
+        let app = Arc::new(AppState::default());
+        let secret = "shared".to_owned();
+        app.webhooks
+            .lock()
+            .await
+            .register(&url, Some(secret.clone()), vec!["milestone".into()])
+            .unwrap();
+        super::spawn(Arc::clone(&app));
+
+        // not subscribed
+        app.events
+            .send(GameEvent::GameStarted {
+                game: 1,
+                puzzle: None,
+            })
+            .ok();
+        let milestone = GameEvent::Milestone {
+            game: 1,
+            tick: 40,
+            length: 10,
+        };
+        app.events.send(milestone).ok();
+
+        let (headers, body) = received.recv().await.unwrap();
+        assert_eq!(headers["x-snapi-event"], "milestone");
+        assert_eq!(
+            headers["x-snapi-signature"].to_str().unwrap(),
+            format!("sha256={}", signature(&secret, &body))
+        );
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["event"], "milestone");
+        assert_eq!(payload["length"], 10);
+        assert!(payload["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn event_is_delivered_over_https() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::rustls::{
+            Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig,
+        };
+
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let der = Certificate(cert.serialize_der().unwrap());
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![der.clone()],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let mut roots = RootCertStore::empty();
+        roots.add(&der).unwrap();
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "https://localhost:{}/hook",
+            listener.local_addr().unwrap().port()
+        );
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let read = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+
+        let mut webhooks = Webhooks::default();
+        webhooks.register(&url, None, Vec::new()).unwrap();
+        let event = GameEvent::GameStarted {
+            game: 1,
+            puzzle: None,
+        };
+        let hook = webhooks.subscribed(&event).remove(0);
+        let client = hyper::Client::builder().build(
+            hyper_rustls::HttpsConnectorBuilder::new()
+                .with_tls_config(client_config)
+                .https_only()
+                .enable_http1()
+                .build(),
+        );
+        super::deliver(client, hook, event.name(), Arc::new(b"{}".to_vec())).await;
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("post /hook "));
+        assert!(request.contains("x-snapi-event: game_started"));
+    }
+}